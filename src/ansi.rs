@@ -0,0 +1,171 @@
+// Rendering of ANSI escape sequences in gopher and text content
+
+use crate::SETTINGS;
+use cursive::theme::{BaseColor, Color, ColorStyle, ColorType, Effect, Style};
+use cursive::utils::lines::spans::LinesIterator;
+use cursive::utils::markup::StyledString;
+
+/// Graphic rendition set by SGR sequences
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Rendition {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    underline: bool,
+    reverse: bool,
+}
+
+impl Rendition {
+    fn style(&self) -> Style {
+        let mut fg = self.fg;
+        // Like most terminals, show bold text in the bright variant
+        // of its color. A lot of ANSI art depends on this.
+        if let (true, Some(Color::Dark(color))) = (self.bold, fg) {
+            fg = Some(Color::Light(color));
+        }
+        let color_type = |color: Option<Color>| match color {
+            Some(color) => ColorType::Color(color),
+            None => ColorType::InheritParent,
+        };
+        let mut style =
+            Style::from_color_style(ColorStyle::new(color_type(fg), color_type(self.bg)));
+        if self.bold {
+            style.effects.insert(Effect::Bold);
+        }
+        if self.underline {
+            style.effects.insert(Effect::Underline);
+        }
+        if self.reverse {
+            style.effects.insert(Effect::Reverse);
+        }
+        style
+    }
+
+    /// Applies the parameters of an SGR sequence
+    fn apply(&mut self, params: &str) {
+        // Empty parameters default to 0
+        let mut codes = params
+            .split([';', ':'])
+            .map(|p| p.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Rendition::default(),
+                1 => self.bold = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                22 => self.bold = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                30..=37 => self.fg = Some(Color::Dark(BaseColor::from_u8(code - 30))),
+                38 => self.fg = extended_color(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Dark(BaseColor::from_u8(code - 40))),
+                48 => self.bg = extended_color(&mut codes),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Light(BaseColor::from_u8(code - 90))),
+                100..=107 => self.bg = Some(Color::Light(BaseColor::from_u8(code - 100))),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Reads a 256 color (`5;n`) or truecolor (`2;r;g;b`) argument of
+/// SGR codes 38 and 48
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::from_256colors(codes.next()?)),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+/// Returns true if the text contains escape sequences
+pub fn has_escapes(text: &str) -> bool {
+    text.contains(['\x1b', '\u{9b}'])
+}
+
+/// Converts text containing ANSI escape sequences into a styled string.
+/// Only SGR sequences are interpreted, all other sequences are dropped.
+pub fn parse(text: &str) -> StyledString {
+    let mut styled = StyledString::new();
+    let mut rendition = Rendition::default();
+    let mut plain = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                // Two character escape sequences are dropped
+                if chars.next() != Some('[') {
+                    continue;
+                }
+            }
+            '\u{9b}' => (),
+            _ => {
+                plain.push(c);
+                continue;
+            }
+        }
+        // Control sequence: parameter bytes followed by intermediate
+        // bytes and a final byte
+        let mut params = String::new();
+        while let Some(&p) = chars.peek() {
+            if !('\x30'..='\x3f').contains(&p) {
+                break;
+            }
+            params.push(p);
+            chars.next();
+        }
+        while chars.next_if(|i| ('\x20'..='\x2f').contains(i)).is_some() {}
+        if chars.next_if(|f| ('\x40'..='\x7e').contains(f)) == Some('m') {
+            if !plain.is_empty() {
+                append(&mut styled, std::mem::take(&mut plain), rendition);
+            }
+            rendition.apply(&params);
+        }
+    }
+    if !plain.is_empty() {
+        append(&mut styled, plain, rendition);
+    }
+    styled
+}
+
+fn append(styled: &mut StyledString, text: String, rendition: Rendition) {
+    if rendition == Rendition::default() {
+        styled.append_plain(text);
+    } else {
+        styled.append_styled(text, rendition.style());
+    }
+}
+
+/// Removes all escape sequences from the text
+pub fn strip(text: &str) -> String {
+    if has_escapes(text) {
+        parse(text).source().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Renders the text with colors or strips escape sequences, depending
+/// on the configuration
+pub fn render(text: &str) -> StyledString {
+    if SETTINGS.read().unwrap().config.render_ansi_colors {
+        parse(text)
+    } else {
+        StyledString::plain(strip(text))
+    }
+}
+
+/// Splits a styled string into lines and wraps them at `width` columns
+pub fn wrap(styled: &StyledString, width: usize) -> Vec<StyledString> {
+    LinesIterator::new(styled, width)
+        .map(|row| {
+            let mut line = StyledString::new();
+            for span in row.resolve_stream(styled) {
+                line.append_styled(span.content, *span.attr);
+            }
+            line
+        })
+        .collect()
+}
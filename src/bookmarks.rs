@@ -1,16 +1,93 @@
+use crate::gophermap::ItemType;
+use roxmltree::{Document, ParsingOptions};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::fs::File as FsFile;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use url::Url;
+use urlencoding::decode_binary;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Bookmark {
     pub title: String,
     pub url: Url,
     pub tags: Vec<String>,
+    /// Folder path separated by slashes, like "Tech/Gemini". Bookmarks
+    /// without folder are shown at the top level of the menu.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub folder: String,
+}
+
+impl Bookmark {
+    /// Returns the names of the nested folders of the bookmark
+    pub fn folders(&self) -> Vec<&str> {
+        self.folder
+            .split('/')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .collect()
+    }
+
+    /// Case insensitive search in title, URL, tags and folder
+    pub fn matches(&self, search: &str) -> bool {
+        let search = search.to_lowercase();
+        self.title.to_lowercase().contains(&search)
+            || self.url.as_str().to_lowercase().contains(&search)
+            || self.folder.to_lowercase().contains(&search)
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(&search))
+    }
+
+    /// Tags are compared case insensitively
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| same_tag(t, tag))
+    }
+}
+
+fn same_tag(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// File formats bookmarks can be exported to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Gophermap,
+    Gemtext,
+    Text,
+    /// Netscape bookmark file as understood by web browsers
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Gophermap,
+        ExportFormat::Gemtext,
+        ExportFormat::Text,
+        ExportFormat::Html,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Gophermap => "Gophermap",
+            ExportFormat::Gemtext => "Gemtext",
+            ExportFormat::Text => "Plain text",
+            ExportFormat::Html => "HTML (Netscape bookmark file)",
+        }
+    }
+
+    /// Default file name of the export
+    pub fn filename(self) -> &'static str {
+        match self {
+            ExportFormat::Gophermap => "gophermap",
+            ExportFormat::Gemtext => "bookmarks.gmi",
+            ExportFormat::Text => "bookmarks.txt",
+            ExportFormat::Html => "bookmarks.html",
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -79,6 +156,182 @@ impl Bookmarks {
         self.entries.clone()
     }
 
+    /// Returns all tags in alphabetical order
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .entries
+            .iter()
+            .flat_map(|e| e.tags.iter().cloned())
+            .collect();
+        tags.sort_by_key(|tag| tag.to_lowercase());
+        tags.dedup_by(|a, b| same_tag(a, b));
+        tags
+    }
+
+    /// Swaps the positions of two bookmarks
+    pub fn swap(&mut self, a: &Url, b: &Url) {
+        let a = self.entries.iter().position(|e| &e.url == a);
+        let b = self.entries.iter().position(|e| &e.url == b);
+        if let (Some(a), Some(b)) = (a, b) {
+            self.entries.swap(a, b);
+            if let Err(why) = self.write_bookmarks_to_file() {
+                warn!("Could not write bookmarks file: {}", why)
+            }
+        }
+    }
+
+    /// Merges imported bookmarks. Bookmarks of known URLs keep their
+    /// title and get the tags of the imported bookmark. Returns the
+    /// number of new bookmarks and the number of merged bookmarks.
+    pub fn import(&mut self, imported: Vec<Bookmark>) -> (usize, usize) {
+        let (mut added, mut merged) = (0, 0);
+        for bookmark in imported {
+            match self.entries.iter_mut().find(|e| e.url == bookmark.url) {
+                Some(existing) => {
+                    for tag in bookmark.tags {
+                        if !existing.has_tag(&tag) {
+                            existing.tags.push(tag);
+                        }
+                    }
+                    if existing.folder.is_empty() {
+                        existing.folder = bookmark.folder;
+                    }
+                    merged += 1;
+                }
+                None => {
+                    self.entries.push(bookmark);
+                    added += 1;
+                }
+            }
+        }
+        if let Err(why) = self.write_bookmarks_to_file() {
+            warn!("Could not write bookmarks file: {}", why)
+        }
+        (added, merged)
+    }
+
+    /// Imports the bookmark file of another client, see `parse_import`
+    pub fn import_file(&mut self, path: &Path) -> Result<(usize, usize), String> {
+        let text = read_to_string(path).map_err(|err| err.to_string())?;
+        Ok(self.import(parse_import(&text)?))
+    }
+
+    /// Returns the bookmarks in the given format
+    pub fn export(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Gophermap => self.export_gophermap(),
+            ExportFormat::Gemtext => self.export_gemtext(),
+            ExportFormat::Text => self.export_text(),
+            ExportFormat::Html => self.export_html(),
+        }
+    }
+
+    /// A gophermap with a menu entry for every bookmark. Links to
+    /// other protocols use the "URL:" selector convention.
+    fn export_gophermap(&self) -> String {
+        let mut out = String::from("iBookmarks\t\terror.host\t1\n");
+        out.push_str("i\t\terror.host\t1\n");
+        for b in &self.entries {
+            let title = b.title.replace(['\t', '\r', '\n'], " ");
+            let host = b.url.host_str().unwrap_or("error.host");
+            let line = match b.url.scheme() {
+                "gopher" => {
+                    let item_type = ItemType::from_url(&b.url);
+                    let path = decode_binary(b.url.path().as_bytes());
+                    let path = String::from_utf8_lossy(&path);
+                    let selector = path.get(2..).unwrap_or_default();
+                    let port = b.url.port().unwrap_or(70);
+                    format!(
+                        "{}{}\t{}\t{}\t{}",
+                        item_type.encode(),
+                        title,
+                        selector,
+                        host,
+                        port
+                    )
+                }
+                "telnet" => format!(
+                    "{}{}\t\t{}\t{}",
+                    ItemType::Telnet.encode(),
+                    title,
+                    host,
+                    b.url.port().unwrap_or(23)
+                ),
+                _ => format!(
+                    "{}{}\tURL:{}\t{}\t{}",
+                    ItemType::Html.encode(),
+                    title,
+                    b.url,
+                    host,
+                    b.url.port_or_known_default().unwrap_or(70)
+                ),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders the about:bookmarks page listing the bookmarks grouped
+    /// by tag
+    pub fn page(&self) -> String {
+        if self.entries.is_empty() {
+            return "# Bookmarks\n\nYou have no bookmarks yet. Use \"Add bookmark\" \
+                    in the Bookmarks menu to bookmark a page.\n"
+                .into();
+        }
+        self.export_gemtext()
+    }
+
+    /// A gemtext link list with a section for every tag. Bookmarks
+    /// with several tags are listed in every section.
+    fn export_gemtext(&self) -> String {
+        let mut out = String::from("# Bookmarks\n");
+        for tag in self.tags() {
+            out.push_str(&format!("\n## {}\n\n", tag));
+            for b in self.entries.iter().filter(|b| b.has_tag(&tag)) {
+                out.push_str(&format!("=> {} {}\n", b.url, b.title));
+            }
+        }
+        let untagged: Vec<&Bookmark> = self.entries.iter().filter(|b| b.tags.is_empty()).collect();
+        if !untagged.is_empty() {
+            out.push_str("\n## Untagged\n\n");
+            for b in untagged {
+                out.push_str(&format!("=> {} {}\n", b.url, b.title));
+            }
+        }
+        out
+    }
+
+    fn export_text(&self) -> String {
+        let mut out = String::new();
+        for b in &self.entries {
+            out.push_str(&format!("{}\n{}\n", b.title, b.url));
+            if !b.folder.is_empty() {
+                out.push_str(&format!("Folder: {}\n", b.folder));
+            }
+            if !b.tags.is_empty() {
+                out.push_str(&format!("Tags: {}\n", b.tags.join(", ")));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn export_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+             <!-- This is an automatically generated file. -->\n\
+             <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+             <TITLE>Bookmarks</TITLE>\n\
+             <H1>Bookmarks</H1>\n",
+        );
+        let entries: Vec<(Vec<&str>, &Bookmark)> =
+            self.entries.iter().map(|b| (b.folders(), b)).collect();
+        html_list(&mut out, &entries, 0);
+        out
+    }
+
     pub fn write_bookmarks_to_file(&mut self) -> std::io::Result<()> {
         let path = Bookmarks::get_bookmark_path();
         info!("Saving bookmarks to file: {:?}", path);
@@ -94,3 +347,275 @@ impl Bookmarks {
         Ok(())
     }
 }
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes a definition list of bookmarks, the folders of the bookmarks
+/// are nested lists
+fn html_list(out: &mut String, entries: &[(Vec<&str>, &Bookmark)], depth: usize) {
+    let indent = "    ".repeat(depth);
+    out.push_str(&format!("{}<DL><p>\n", indent));
+    let mut folders = Vec::new();
+    for (path, b) in entries {
+        match path.first() {
+            None => {
+                out.push_str(&format!(
+                    "{}    <DT><A HREF=\"{}\"",
+                    indent,
+                    escape_html(b.url.as_str())
+                ));
+                if !b.tags.is_empty() {
+                    out.push_str(&format!(" TAGS=\"{}\"", escape_html(&b.tags.join(","))));
+                }
+                out.push_str(&format!(">{}</A>\n", escape_html(&b.title)));
+            }
+            Some(folder) if !folders.contains(folder) => {
+                folders.push(*folder);
+                out.push_str(&format!(
+                    "{}    <DT><H3>{}</H3>\n",
+                    indent,
+                    escape_html(folder)
+                ));
+                let children: Vec<(Vec<&str>, &Bookmark)> = entries
+                    .iter()
+                    .filter(|(path, _)| path.first() == Some(folder))
+                    .map(|(path, b)| (path[1..].to_vec(), *b))
+                    .collect();
+                html_list(out, &children, depth + 1);
+            }
+            Some(_) => (),
+        }
+    }
+    out.push_str(&format!("{}</DL><p>\n", indent));
+}
+
+/// Reads the bookmarks of other clients: Lagrange (bookmarks.ini),
+/// Amfora (bookmarks.xml or the older bookmarks.toml), Bombadillo
+/// (.bombadillo.ini), bookmark files of ncgopher and gemtext link
+/// lists as written by Castor or by the gemtext export.
+pub fn parse_import(text: &str) -> Result<Vec<Bookmark>, String> {
+    let bookmarks = if text.trim_start().starts_with('<') {
+        parse_xbel(text)?
+    } else if let Ok(table) = toml::from_str::<toml::Table>(text) {
+        if let Some(toml::Value::Table(bookmarks)) = table.get("bookmarks") {
+            parse_amfora_toml(bookmarks)
+        } else if let Some(toml::Value::Array(bookmarks)) = table.get("bookmark") {
+            bookmarks
+                .iter()
+                .filter_map(|b| b.clone().try_into().ok())
+                .collect()
+        } else {
+            parse_lagrange(&table)
+        }
+    } else if text.lines().any(|line| line.trim() == "[BOOKMARKS]") {
+        parse_bombadillo(text)
+    } else {
+        parse_gemtext(text)
+    };
+    if bookmarks.is_empty() {
+        Err("no bookmarks found".into())
+    } else {
+        Ok(bookmarks)
+    }
+}
+
+fn bookmark(title: &str, url: Url, tags: Vec<String>, folder: String) -> Bookmark {
+    let title = match title.trim() {
+        "" => url.to_string(),
+        title => title.to_string(),
+    };
+    Bookmark {
+        title,
+        url,
+        tags,
+        folder,
+    }
+}
+
+/// Lagrange stores every bookmark and folder as a numbered table.
+/// Folders have no URL and bookmarks refer to their folder by number.
+fn parse_lagrange(table: &toml::Table) -> Vec<Bookmark> {
+    // Tags Lagrange uses internally for special bookmarks
+    const INTERNAL_TAGS: [&str; 7] = [
+        "homepage",
+        "remote",
+        "remotesource",
+        "subscribed",
+        "headings",
+        "ignoreweb",
+        "usericon",
+    ];
+    let str_value = |entry: &toml::Value, key: &str| {
+        entry
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let parent = |entry: &toml::Value| {
+        entry
+            .get("parent")
+            .and_then(|v| v.as_integer())
+            .map(|p| p.to_string())
+    };
+    let folder = |entry: &toml::Value| {
+        let mut path = Vec::new();
+        let mut next = parent(entry);
+        // the depth limit protects against cycles
+        while let Some(folder) = next
+            .and_then(|id| table.get(&id))
+            .filter(|_| path.len() < 32)
+        {
+            path.insert(0, str_value(folder, "title").replace('/', "-"));
+            next = parent(folder);
+        }
+        path.join("/")
+    };
+    let mut entries: Vec<(i64, Bookmark)> = table
+        .values()
+        .filter_map(|entry| {
+            let url = Url::parse(&str_value(entry, "url")).ok()?;
+            let tags = str_value(entry, "tags")
+                .split_whitespace()
+                .filter(|tag| !INTERNAL_TAGS.contains(tag))
+                .map(String::from)
+                .collect();
+            let order = entry.get("order").and_then(|v| v.as_integer());
+            let bookmark = bookmark(&str_value(entry, "title"), url, tags, folder(entry));
+            Some((order.unwrap_or(i64::MAX), bookmark))
+        })
+        .collect();
+    entries.sort_by_key(|(order, _)| *order);
+    entries.into_iter().map(|(_, bookmark)| bookmark).collect()
+}
+
+/// Decodes base32 (RFC 4648) ignoring case and padding
+fn decode_base32(text: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = ALPHABET.iter().position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Older versions of Amfora map the URLs to the titles. The URLs are
+/// base32 encoded because the keys are lowercased.
+fn parse_amfora_toml(bookmarks: &toml::Table) -> Vec<Bookmark> {
+    bookmarks
+        .iter()
+        .filter_map(|(key, title)| {
+            let url = Url::parse(key).ok().or_else(|| {
+                let decoded = String::from_utf8(decode_base32(key)?).ok()?;
+                Url::parse(&decoded).ok()
+            })?;
+            Some(bookmark(title.as_str()?, url, Vec::new(), String::new()))
+        })
+        .collect()
+}
+
+/// XML Bookmark Exchange Language as written by Amfora
+fn parse_xbel(text: &str) -> Result<Vec<Bookmark>, String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(text, options).map_err(|err| err.to_string())?;
+    let title = |node: roxmltree::Node| {
+        node.children()
+            .find(|child| child.tag_name().name() == "title")
+            .and_then(|child| child.text())
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let mut bookmarks = Vec::new();
+    for node in document.descendants() {
+        if node.tag_name().name() != "bookmark" {
+            continue;
+        }
+        let Some(url) = node
+            .attribute("href")
+            .and_then(|href| Url::parse(href).ok())
+        else {
+            continue;
+        };
+        let mut folders: Vec<String> = node
+            .ancestors()
+            .filter(|a| a.tag_name().name() == "folder")
+            .map(|folder| title(folder).replace('/', "-"))
+            .collect();
+        folders.reverse();
+        bookmarks.push(bookmark(&title(node), url, Vec::new(), folders.join("/")));
+    }
+    Ok(bookmarks)
+}
+
+/// Bombadillo keeps its bookmarks as "title=URL" lines in the
+/// BOOKMARKS section of its configuration
+fn parse_bombadillo(text: &str) -> Vec<Bookmark> {
+    let mut section = "";
+    let mut bookmarks = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line;
+            continue;
+        }
+        if section != "[BOOKMARKS]" {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        // Accept "URL=title" too
+        let (title, url) = match (Url::parse(value.trim()), Url::parse(key.trim())) {
+            (Ok(url), _) => (key, url),
+            (_, Ok(url)) => (value, url),
+            _ => continue,
+        };
+        bookmarks.push(bookmark(title, url, Vec::new(), String::new()));
+    }
+    bookmarks
+}
+
+/// Reads the link lines of a gemtext page. The text of level 2 and
+/// 3 headings is used as tag of the links below it.
+fn parse_gemtext(text: &str) -> Vec<Bookmark> {
+    let mut tag = None;
+    let mut bookmarks = Vec::new();
+    for line in text.lines() {
+        if let Some(heading) = line.strip_prefix("##") {
+            let heading = heading.trim_start_matches('#').trim();
+            tag = match heading {
+                // The section of the gemtext export without tags
+                "" | "Untagged" => None,
+                heading => Some(heading.split_whitespace().collect::<Vec<_>>().join("-")),
+            };
+            continue;
+        }
+        let Some(link) = line.strip_prefix("=>") else {
+            continue;
+        };
+        let link = link.trim();
+        let (url, title) = link.split_once(char::is_whitespace).unwrap_or((link, ""));
+        let Ok(url) = Url::parse(url) else {
+            continue;
+        };
+        let tags = tag.iter().cloned().collect();
+        bookmarks.push(bookmark(title, url, tags, String::new()));
+    }
+    bookmarks
+}
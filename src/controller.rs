@@ -1,20 +1,25 @@
-use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::bookmarks::{Bookmark, Bookmarks, ExportFormat};
 use crate::certificates::Certificates;
 use crate::clientcertificates::{ClientCertificate, ClientCertificates};
-use crate::gemini::GeminiType;
+use crate::feeds::{Feed, Feeds, SourceKind, Subscription};
+use crate::gemini::{GeminiType, PreformattedBlock};
 use crate::gophermap::{GopherMapEntry, ItemType};
-use crate::history::{History, HistoryEntry};
+use crate::history::{History, HistoryEntry, Session};
+use crate::readinglist::{CachedPage, ReadingList, ReadingListItem};
+use crate::session::{SavedPage, SavedSession, SavedTab};
+use crate::tabs::Tab;
+use crate::ui::imageview;
 use crate::ui::layout::Layout;
-use crate::ui::setup::move_to_next_item;
+use crate::ui::setup::{move_to_next_item, HISTORY_MENU_ITEMS};
 use crate::url_tools::{download_filename_from_url, human_readable_url, normalize_domain};
 use crate::SETTINGS;
 use base64::engine::general_purpose;
 use base64::Engine;
 use cursive::{
-    theme::ColorStyle,
+    theme::{ColorStyle, Style},
     utils::{lines::simple::LinesIterator, markup::StyledString},
     view::{Nameable, Resizable},
-    views::{Dialog, EditView, NamedView, ResizedView, ScrollView, SelectView},
+    views::{Dialog, EditView, NamedView, ResizedView, ScrollView, SelectView, TextView},
     Cursive, CursiveRunnable,
 };
 use linkify::{LinkFinder, LinkKind};
@@ -23,17 +28,18 @@ use rcgen::{date_time_ymd, CertificateParams, DistinguishedName, DnType, KeyPair
 use rustls::crypto::{ring as provider, CryptoProvider};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls_pemfile::{read_one, Item};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::error::Error;
 use std::fs::OpenOptions;
 use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::iter;
-use std::net::TcpStream;
-use std::path::Path;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
+use std::time::Duration;
 use stringreader::StringReader;
 use time::format_description::well_known::Rfc3339;
 use time::{Date, OffsetDateTime};
@@ -48,6 +54,20 @@ pub enum Direction {
 }
 
 const HISTORY_LEN: usize = 10;
+/// Number of entries shown on the about:feeds page
+const FEED_PAGE_LEN: usize = 200;
+/// Number of entries shown on the about:history page
+const HISTORY_PAGE_LEN: usize = 500;
+/// Redirects followed when updating feeds
+const MAX_REDIRECTS: usize = 5;
+/// Bytes read at most from a response without user interaction
+const MAX_BODY_LEN: u64 = 16 * 1024 * 1024;
+/// Seconds to wait for a host before a request fails
+const REQUEST_TIMEOUT: u64 = 30;
+/// Seconds to wait for a gopher server before its mirror is tried
+const MIRROR_TIMEOUT: u64 = 5;
+/// Seconds between crash-recovery snapshots of the open tabs
+const SNAPSHOT_INTERVAL: u64 = 30;
 
 mod danger {
     use rustls::client::danger::HandshakeSignatureValid;
@@ -134,28 +154,53 @@ type SenderCursive = crossbeam_channel::Sender<Box<dyn FnOnce(&mut Cursive) + 's
 pub struct Controller {
     sender: SenderCursive,
     /// The browsing history
-    pub(crate) history: Rc<Mutex<History>>,
+    pub(crate) history: Arc<Mutex<History>>,
     /// Bookmarks
     pub(crate) bookmarks: Arc<Mutex<Bookmarks>>,
     /// ClientCertificates (gemini)
     pub(crate) client_certificates: Arc<Mutex<ClientCertificates>>,
     /// Known hosts for gemini TOFU
     certificates: Arc<Mutex<Certificates>>,
+    /// Feed subscriptions, watched gophermaps and their entries
+    pub(crate) feeds: Arc<Mutex<Feeds>>,
+    /// Pages saved to be read later
+    pub(crate) reading_list: Arc<Mutex<ReadingList>>,
     /// Current textual content
     content: Arc<Mutex<String>>,
     /// Current URL
     pub current_url: Arc<Mutex<Url>>,
+    /// Title of the current page if it has one
+    pub(crate) current_title: Option<String>,
     /// When the user triggers several requests, only the last request
     /// will be displayed, the other will be canceled.
     last_request_id: Arc<Mutex<i64>>,
     /// Number of redirects in gemini protocol
     redirect_count: Arc<Mutex<i32>>,
+    /// Redundant servers for gopher URLs, taken from the (+) lines
+    /// of visited gophermaps
+    mirrors: Arc<Mutex<HashMap<Url, Vec<Url>>>>,
     /// Message shown in statusbar
     message: Arc<RwLock<String>>,
+    /// URL and type of the content shown in the gemini view
+    gemini_page: (Url, GeminiType),
+    /// Display state of the preformatted blocks in the gemini view
+    preformatted: Vec<PreformattedBlock>,
+    /// First row to show once the next page is rendered, set when an
+    /// item of the reading list is opened
+    scroll_to: Option<usize>,
     // Current search string
     current_search: String,
     // Current search results
     pub current_search_results: Vec<usize>,
+    /// True while the page of the current tab is requested
+    loading: bool,
+    /// True while the subscriptions are updated in the background
+    updating_feeds: bool,
+    /// All open tabs. While a tab is shown, its state is kept in the
+    /// fields above instead.
+    tabs: Vec<Tab>,
+    current_tab: usize,
+    next_tab_id: usize,
 }
 
 impl Controller {
@@ -165,33 +210,43 @@ impl Controller {
 
         let mut controller = Controller {
             sender: app.cb_sink().clone(),
-            history: Rc::new(Mutex::new(History::new()?)),
+            history: Arc::new(Mutex::new(History::new()?)),
             bookmarks: Arc::new(Mutex::new(Bookmarks::new())),
             client_certificates: Arc::new(Mutex::new(ClientCertificates::new())),
             certificates: Arc::new(Mutex::new(Certificates::new())),
+            feeds: Arc::new(Mutex::new(Feeds::new()?)),
+            reading_list: Arc::new(Mutex::new(ReadingList::new()?)),
             content: Arc::new(Mutex::new(String::new())),
             current_url: Arc::new(Mutex::new(Url::parse("about:blank").unwrap())),
+            current_title: None,
             last_request_id: Arc::new(Mutex::new(0)),
             redirect_count: Arc::new(Mutex::new(0)),
+            mirrors: Arc::new(Mutex::new(HashMap::new())),
             message: app
                 .find_name::<crate::ui::statusbar::StatusBar>("statusbar")
                 .unwrap()
                 .get_message(),
+            gemini_page: (Url::parse("about:blank").unwrap(), GeminiType::Text),
+            preformatted: Vec::new(),
+            scroll_to: None,
             current_search: String::new(),
             current_search_results: Vec::new(),
+            loading: false,
+            updating_feeds: false,
+            tabs: vec![Tab::new(0, url.clone())],
+            current_tab: 0,
+            next_tab_id: 1,
         };
 
-        let mut entries = controller
+        let entries = controller
             .history
             .lock()
             .unwrap()
             .get_latest_history(HISTORY_LEN)
             .expect("Could not get latest history");
-        entries.reverse();
         crate::ui::setup::setup_history_menu(app, &entries);
 
-        let mut entries = controller.bookmarks.lock().unwrap().get_bookmarks();
-        entries.reverse();
+        let entries = controller.bookmarks.lock().unwrap().get_bookmarks();
         crate::ui::setup::setup_bookmark_menu(app, &entries);
 
         // open initial page
@@ -199,6 +254,33 @@ impl Controller {
 
         app.set_user_data(controller);
 
+        // Update feeds and phlogs in the background until the
+        // application is closed
+        let sender = app.cb_sink().clone();
+        thread::spawn(move || loop {
+            let minutes = SETTINGS.read().unwrap().config.feed_update_interval;
+            if minutes > 0 {
+                let update = sender.send(Box::new(|app| {
+                    let controller = app.user_data::<Controller>().expect("controller missing");
+                    controller.update_feeds();
+                }));
+                if update.is_err() {
+                    break;
+                }
+            }
+            thread::sleep(Duration::from_secs(60 * u64::from(minutes.max(1))));
+        });
+
+        // Write a snapshot of the open tabs periodically, so they can be
+        // restored after a crash
+        let sender = app.cb_sink().clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(SNAPSHOT_INTERVAL));
+            if sender.send(Box::new(Controller::write_snapshot)).is_err() {
+                break;
+            }
+        });
+
         info!("Controller::new() done");
 
         Ok(())
@@ -580,7 +662,7 @@ impl Controller {
                     let mime = meta.parse::<Mime>()
                         .unwrap_or_else(|_| "text/gemini".parse().unwrap());
 
-                    if mime.type_() == "text" {
+                    if mime.type_() == "text" || crate::feeds::is_xml_feed(&mime) {
                         // some kind of text. First check encoding.
                         let encoding = mime.get_param("charset")
                             // default is UTF-8
@@ -613,11 +695,7 @@ impl Controller {
                             0
                         });
 
-                        let gemini_type = match mime.subtype().as_str() {
-                            "gemini" => GeminiType::Gemini,
-                            // FIXME: add HTML handler
-                            _ => GeminiType::Text,
-                        };
+                        let gemini_type = Controller::gemini_type(&mime);
 
                         let s = String::from_utf8_lossy(&buf).into_owned();
                         sender.send(Box::new(move |app|{
@@ -662,8 +740,12 @@ impl Controller {
                                             local_filename, total_written
                                         ));
                                         if mime.type_() == "image" {
-                                            let path = Path::new(&local_filename);
-                                            controller.open_image_from_file(path).ok();
+                                            let path = PathBuf::from(&local_filename);
+                                            if SETTINGS.read().unwrap().config.image_preview {
+                                                controller.preview_image_file(url, path);
+                                            } else {
+                                                controller.open_image_from_file(&path).ok();
+                                            }
                                         }
                                     }))
                                     .unwrap();
@@ -782,6 +864,19 @@ impl Controller {
         });
     }
 
+    /// Returns how text served via gemini is rendered
+    fn gemini_type(mime: &Mime) -> GeminiType {
+        match mime.subtype().as_str() {
+            _ if crate::feeds::is_xml_feed(mime) => GeminiType::Feed,
+            "gemini" => GeminiType::Gemini,
+            "html" => GeminiType::Html,
+            "markdown" => GeminiType::Markdown,
+            // might be a feed, otherwise shown as text
+            "xml" => GeminiType::Feed,
+            _ => GeminiType::Text,
+        }
+    }
+
     fn fetch_url(&self, url: Url, item_type: ItemType, index: usize) {
         // index is the position in the text (used when navigating back or reloading)
         if !SETTINGS.read().unwrap().config.disable_history {
@@ -812,79 +907,49 @@ impl Controller {
             path = "".to_string();
         }
 
-        let server_details = format!("{}:{}", server, port);
+        // The primary server is tried first, then all redundant servers
+        // in the order they were listed in the gophermap
+        let mut servers = vec![(server, port)];
+        if let Some(mirrors) = self.mirrors.lock().unwrap().get(&url) {
+            servers.extend(mirrors.iter().filter_map(|mirror| {
+                let host = mirror.host_str()?.to_string();
+                Some((host, mirror.port().unwrap_or(70)))
+            }));
+        }
 
         let request_id_ref = self.last_request_id.clone();
         let sender = self.sender.clone();
 
         let config = Controller::get_tls_client_config(&None, &None);
         thread::spawn(move || {
-            let mut use_tls = false;
             let mut buf = vec![];
-            // TLS-support. If non-standard-port, try to connect with TLS
-            if port != 70 {
-                let server_name = server.clone().try_into().unwrap();
-                let mut conn =
-                    rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
-
-                let stream = TcpStream::connect(server_details.clone());
-                if let Ok(mut stream) = stream {
-                    match conn.complete_io(&mut stream) {
-                        Err(err) => {
-                            error!("Could not complete TLS handshake: {:?}", err);
-                            use_tls = false;
-                        }
-                        Ok(_) => {
-                            info!("Now connected with tls");
-                            use_tls = true;
-                        }
-                    }
-                    let mut tls = rustls::Stream::new(&mut conn, &mut stream);
-                    if use_tls {
-                        if let Err(err) = tls.write_all(format!("{}\r\n", path).as_bytes()) {
-                            // Something went wrong, could not write write request URL
-                            use_tls = false;
-                            warn!(
-                                "Could not write request URL for address {}: {:?}",
-                                server_details.clone(),
-                                err
-                            );
-                        }
-                    }
-                    if use_tls {
-                        let mut bufr = BufReader::new(tls);
-                        loop {
-                            match bufr.read_to_end(&mut buf) {
-                                Ok(_) => break,
-                                Err(e) => {
-                                    client_msg!(sender, "I/O error: {}", e);
-                                }
-                            };
-                        }
-                    }
+            let mut served_by = None;
+            for (i, (server, port)) in servers.iter().enumerate() {
+                // give up early on servers that have a mirror to try next
+                let timeout = if i + 1 < servers.len() {
+                    Duration::from_secs(MIRROR_TIMEOUT)
                 } else {
-                    use_tls = false;
-                }
-            }
-            // TLS connection failed or still on port 70
-            if !use_tls {
-                match TcpStream::connect(server_details.clone()) {
-                    Ok(mut stream) => {
-                        write!(stream, "{}\r\n", path).unwrap();
-                        loop {
-                            match stream.read_to_end(&mut buf) {
-                                Ok(_) => break,
-                                Err(e) => {
-                                    client_msg!(sender, "I/O error: {}", e);
-                                }
-                            }
+                    Duration::from_secs(REQUEST_TIMEOUT)
+                };
+                match Controller::gopher_request(server, *port, &path, config.clone(), timeout) {
+                    Ok(content) => {
+                        buf = content;
+                        if i > 0 {
+                            served_by = Some(format!("{}:{}", server, port));
                         }
+                        break;
+                    }
+                    Err(e) if i + 1 < servers.len() => {
+                        warn!(
+                            "Couldn't connect to {}:{}: {}, trying mirror",
+                            server, port, e
+                        );
                     }
                     Err(e) => {
                         client_msg!(sender, "Couldn't connect to server: {}", e);
                         return;
                     }
-                };
+                }
             }
 
             let guard = request_id_ref.lock().unwrap();
@@ -897,14 +962,128 @@ impl Controller {
             sender
                 .send(Box::new(move |app| {
                     let controller = app.user_data::<Controller>().expect("controller missing");
-                    controller.set_message(url.as_str());
+                    match served_by {
+                        Some(mirror) => controller.set_message(&format!(
+                            "{} (primary server unavailable, served by mirror {})",
+                            url, mirror
+                        )),
+                        None => controller.set_message(url.as_str()),
+                    }
                     controller.clear_search();
+                    if item_type.is_dir() {
+                        controller.gophermap_fetched(&url, &s);
+                    }
                     controller.set_gopher_content(item_type, s, index);
                 }))
                 .unwrap();
         });
     }
 
+    /// Requests the given selector from a gopher server and returns
+    /// the raw response. On non-standard ports a TLS connection is
+    /// tried first, falling back to plain TCP. Connecting, reading and
+    /// writing give up after `timeout`.
+    fn gopher_request(
+        server: &str,
+        port: u16,
+        path: &str,
+        config: rustls::ClientConfig,
+        timeout: Duration,
+    ) -> std::io::Result<Vec<u8>> {
+        let server_details = format!("{}:{}", server, port);
+        let mut buf = vec![];
+        // TLS-support. If non-standard-port, try to connect with TLS
+        if port != 70 {
+            let server_name = server.to_string().try_into().unwrap();
+            let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
+
+            if let Ok(mut stream) = Controller::connect(&server_details, timeout) {
+                match conn.complete_io(&mut stream) {
+                    Err(err) => {
+                        error!("Could not complete TLS handshake: {:?}", err);
+                    }
+                    Ok(_) => {
+                        info!("Now connected with tls");
+                        let mut tls = rustls::Stream::new(&mut conn, &mut stream);
+                        match tls.write_all(format!("{}\r\n", path).as_bytes()) {
+                            Ok(_) => {
+                                BufReader::new(tls).read_to_end(&mut buf)?;
+                                return Ok(buf);
+                            }
+                            Err(err) => {
+                                // Something went wrong, could not write write request URL
+                                warn!(
+                                    "Could not write request URL for address {}: {:?}",
+                                    server_details, err
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // TLS connection failed or still on port 70
+        let mut stream = Controller::connect(&server_details, timeout)?;
+        write!(stream, "{}\r\n", path)?;
+        stream.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Connects to the first address of a host that answers within
+    /// `timeout`. Reads and writes on the stream time out as well.
+    fn connect(server_details: &str, timeout: Duration) -> std::io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in server_details.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "could not resolve host")
+        }))
+    }
+
+    /// Fetches an image from a gopher server and shows a preview. If
+    /// the image cannot be decoded, it is opened in the images viewer.
+    fn fetch_image(&self, url: Url) {
+        self.set_message("Loading image...");
+        let port = url.port().unwrap_or(70);
+        let server = url.host_str().expect("no host").to_string();
+        let binary = decode_binary(url.path().as_bytes());
+        let path = String::from_utf8_lossy(&binary)
+            .get(2..)
+            .unwrap_or_default()
+            .to_string();
+        let sender = self.sender.clone();
+        let config = Controller::get_tls_client_config(&None, &None);
+        thread::spawn(move || {
+            let timeout = Duration::from_secs(REQUEST_TIMEOUT);
+            let buf = match Controller::gopher_request(&server, port, &path, config, timeout) {
+                Ok(buf) => buf,
+                Err(e) => {
+                    client_msg!(sender, "Couldn't connect to server: {}", e);
+                    return;
+                }
+            };
+            let image = imageview::decode(&buf);
+            sender
+                .send(Box::new(move |app| match image {
+                    Ok(image) => crate::ui::dialogs::image_preview(app, url, image, None),
+                    Err(err) => {
+                        let controller = app.user_data::<Controller>().expect("controller missing");
+                        controller.set_message(&format!("Could not decode image: {}", err));
+                        controller.open_command("image_command", url).unwrap();
+                    }
+                }))
+                .unwrap();
+        });
+    }
+
     fn fetch_binary_url(&mut self, url: Url, item_type: ItemType, local_filename: String) {
         self.set_message("Downloading binary file...");
 
@@ -1013,6 +1192,8 @@ impl Controller {
                             if item_type == ItemType::Gif || item_type == ItemType::Image {
                                 let path = Path::new(&local_filename);
                                 controller.open_image_from_file(path).ok();
+                            } else if item_type.is_encoded() {
+                                controller.decode_download(&local_filename, item_type);
                             }
                         }))
                         .unwrap();
@@ -1024,6 +1205,81 @@ impl Controller {
         });
     }
 
+    /// Decodes a downloaded uuencoded or BinHex file and saves the
+    /// result with its original name in the download directory.
+    fn decode_download(&mut self, local_filename: &str, item_type: ItemType) {
+        let raw = match std::fs::read(local_filename) {
+            Ok(raw) => raw,
+            Err(err) => {
+                self.set_message(&format!(
+                    "Unable to read file '{}': {}",
+                    local_filename, err
+                ));
+                return;
+            }
+        };
+        let decoded = match item_type {
+            ItemType::Uuencoded => crate::decode::uudecode(&raw),
+            ItemType::BinHex => crate::decode::binhex(&raw),
+            _ => return,
+        };
+        let file = match decoded {
+            Ok(file) => file,
+            Err(err) => {
+                self.set_message(&format!("Could not decode '{}': {}", local_filename, err));
+                return;
+            }
+        };
+
+        // Never use a path stored in the encoded file
+        let name = file.name.replace(['/', '\\'], "_");
+        let name = if name.is_empty() || name == "." || name == ".." {
+            "download".to_string()
+        } else {
+            name
+        };
+        let download_path = SETTINGS.read().unwrap().config.download_path.clone();
+        let path = Path::new(&download_path).join(&name);
+        let keep_raw = SETTINGS.read().unwrap().config.keep_raw_downloads;
+
+        let mut options = OpenOptions::new();
+        options.write(true);
+        if !keep_raw && path == Path::new(local_filename) {
+            // the raw file will be replaced by the decoded file
+            options.truncate(true);
+        } else {
+            // make sure to not clobber downloaded files
+            options.create_new(true);
+        }
+        #[cfg(unix)]
+        if let Some(mode) = file.mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode & 0o777);
+        }
+        let result = options
+            .open(&path)
+            .and_then(|mut f| f.write_all(&file.data));
+        match result {
+            Ok(_) => {
+                if !keep_raw && path != Path::new(local_filename) {
+                    if let Err(err) = std::fs::remove_file(local_filename) {
+                        warn!("Could not remove raw file {}: {}", local_filename, err);
+                    }
+                }
+                self.set_message(&format!(
+                    "File decoded: {} ({} bytes)",
+                    path.display(),
+                    file.data.len()
+                ));
+            }
+            Err(err) => self.set_message(&format!(
+                "Unable to open file '{}': {}",
+                path.display(),
+                err
+            )),
+        }
+    }
+
     pub fn open_url(&mut self, url: Url, add_to_history: bool, index: usize) {
         if !SETTINGS.read().unwrap().config.disable_history {
             info!("Open_url: {} position {}", url, index);
@@ -1031,6 +1287,10 @@ impl Controller {
         if add_to_history {
             self.add_to_history(url.clone(), index);
         }
+        self.scroll_to = None;
+        if let Err(err) = self.feeds.lock().unwrap().mark_read(&url) {
+            warn!("Could not mark feed entry as read: {}", err);
+        }
         *self.current_url.lock().unwrap() = url.clone();
         match url.scheme() {
             "finger" => self.open_finger_address(url.clone(), index),
@@ -1111,6 +1371,18 @@ impl Controller {
             "help" => include_str!("about/help.gmi").into(),
             "release-notes" => include_str!("about/release-notes.gmi").into(),
             "sites" => include_str!("about/sites.gmi").into(),
+            "bookmarks" => self.bookmarks.lock().unwrap().page(),
+            "history" => match self
+                .history
+                .lock()
+                .unwrap()
+                .get_latest_history(HISTORY_PAGE_LEN)
+            {
+                Ok(entries) => crate::history::page(&entries),
+                Err(err) => format!("# History\n\nCould not read history: {}", err),
+            },
+            "feeds" => self.feeds_page(SourceKind::Feed, url.query() == Some("mark-read")),
+            "phlogs" => self.feeds_page(SourceKind::Phlog, url.query() == Some("mark-read")),
             "error" => "An error occured.".into(),
             "license" => concat!(
                 include_str!("about/license_header.gmi"),
@@ -1127,23 +1399,52 @@ impl Controller {
         self.clear_search();
     }
 
+    /// Renders about:feeds or about:phlogs, optionally marking all
+    /// entries of the kind as read first
+    fn feeds_page(&self, kind: SourceKind, mark_read: bool) -> String {
+        let mut feeds = self.feeds.lock().unwrap();
+        if mark_read {
+            if let Err(err) = feeds.mark_all_read(kind) {
+                warn!("Could not mark entries as read: {}", err);
+            }
+        }
+        let subscriptions = feeds
+            .subscriptions()
+            .map_or(0, |s| s.iter().filter(|s| s.kind == kind).count());
+        match feeds.latest_entries(kind, FEED_PAGE_LEN) {
+            Ok(entries) => crate::feeds::page(kind, &entries, subscriptions),
+            Err(err) => format!("# Feeds\n\nCould not read feeds: {}", err),
+        }
+    }
+
     pub fn open_gopher_address(&mut self, url: Url, item_type: ItemType, index: usize) {
         self.set_message("Loading ...");
-        if item_type.is_download() {
+        if item_type.is_image() && SETTINGS.read().unwrap().config.image_preview {
+            self.fetch_image(url);
+        } else if item_type.is_download() {
             let filename = download_filename_from_url(&url);
             self.fetch_binary_url(url, item_type, filename);
         } else {
+            self.loading = true;
             self.fetch_url(url, item_type, index);
         }
     }
 
     /// Renders a gophermap
     fn set_gopher_content(&mut self, item_type: ItemType, content: String, index: usize) {
+        self.loading = false;
         let mut guard = self.content.lock().unwrap();
         guard.clear();
         guard.push_str(content.as_str());
         drop(guard);
 
+        if item_type.is_html() {
+            self.clear_search();
+            let url = self.current_url.lock().unwrap().clone();
+            self.set_gemini_content(url, GeminiType::Html, content, index, None);
+            return;
+        }
+
         if item_type.is_text() {
             self.clear_search();
             let human_url = human_readable_url(&self.current_url.lock().unwrap());
@@ -1163,9 +1464,19 @@ impl Controller {
                 })
                 .collect::<Vec<String>>()
                 .join("\n");
+            let gemini_type = if human_url.to_lowercase().ends_with(".md") {
+                GeminiType::Markdown
+            } else if crate::feeds::has_feed_extension(&human_url)
+                || human_url.to_lowercase().ends_with(".xml")
+            {
+                // generic XML files are shown as text if they are no feed
+                GeminiType::Feed
+            } else {
+                GeminiType::Text
+            };
             self.set_gemini_content(
                 Url::parse(&human_url).unwrap(),
-                GeminiType::Text,
+                gemini_type,
                 content_without_dots,
                 index,
                 None,
@@ -1173,6 +1484,8 @@ impl Controller {
             return;
         }
 
+        self.set_page_title(crate::gophermap::title(&content));
+
         // ensure gopher view is focused before setting content
         self.sender
             .send(Box::new(|app| {
@@ -1225,6 +1538,17 @@ impl Controller {
                     }
                     if l != "." {
                         match GopherMapEntry::parse(l.to_string()) {
+                            Ok(gl) if gl.item_type.is_redundant_server() => {
+                                // Group redundant servers with the item above,
+                                // an info line in between ends the group
+                                match gophermap
+                                    .last_mut()
+                                    .filter(|e: &&mut GopherMapEntry| !e.item_type.is_inline())
+                                {
+                                    Some(primary) => primary.add_mirror(&gl),
+                                    None => warn!("Redundant server without primary item"),
+                                }
+                            }
                             Ok(gl) => {
                                 gophermap.push(gl);
                             }
@@ -1234,25 +1558,29 @@ impl Controller {
                         };
                     }
                 }
+                let controller = app.user_data::<Controller>().expect("controller missing");
+                let mut mirrors = controller.mirrors.lock().unwrap();
+                for entry in gophermap.iter().filter(|e| !e.mirrors.is_empty()) {
+                    mirrors.insert(entry.url.clone(), entry.mirrors.clone());
+                }
+                drop(mirrors);
+
                 for l in gophermap {
                     let entry = l.clone();
 
-                    let label = entry.clone().label();
-                    if entry.item_type == ItemType::Inline && label.len() > viewport_width {
-                        for row in LinesIterator::new(&label, viewport_width) {
-                            let mut formatted = StyledString::new();
-                            let label = format!(
-                                "{}  {}",
-                                ItemType::as_str(entry.item_type),
-                                &label[row.start..row.end]
-                            );
-                            formatted.append(label);
+                    let label = entry.styled_label();
+                    if entry.item_type == ItemType::Inline && label.width() > viewport_width {
+                        for row in crate::ansi::wrap(&label, viewport_width) {
+                            let mut formatted = StyledString::plain(format!(
+                                "{}  ",
+                                ItemType::as_str(entry.item_type)
+                            ));
+                            formatted.append(row);
                             view.add_item(formatted, l.clone());
                         }
                     } else {
-                        let mut formatted = StyledString::new();
-                        let label =
-                            format!("{}  {}", ItemType::as_str(entry.item_type), entry.label());
+                        let mut formatted =
+                            StyledString::plain(format!("{}  ", ItemType::as_str(entry.item_type)));
                         formatted.append(label);
                         view.add_item(formatted, l.clone());
                     }
@@ -1296,14 +1624,25 @@ impl Controller {
                                     controller.fetch_url(url, ItemType::Dir, 0);
                                 }),
                         );
+                    } else if entry.item_type.is_cso() {
+                        crate::ui::dialogs::cso_query(app, entry.url.clone());
                     } else if entry.item_type.is_html() {
-                        controller
-                            .open_command("html_command", entry.url.clone())
-                            .unwrap();
+                        if entry.url.scheme() == "gopher" {
+                            // HTML served by the gopher server itself
+                            controller.open_url(entry.url.clone(), true, 0);
+                        } else {
+                            controller
+                                .open_command("html_command", entry.url.clone())
+                                .unwrap();
+                        }
                     } else if entry.item_type.is_image() {
-                        controller
-                            .open_command("image_command", entry.url.clone())
-                            .unwrap();
+                        if SETTINGS.read().unwrap().config.image_preview {
+                            controller.fetch_image(entry.url.clone());
+                        } else {
+                            controller
+                                .open_command("image_command", entry.url.clone())
+                                .unwrap();
+                        }
                     } else if entry.item_type.is_telnet() {
                         controller
                             .open_command("telnet_command", entry.url.clone())
@@ -1315,6 +1654,8 @@ impl Controller {
                     }
                 });
                 view.set_selection(index);
+                drop(view);
+                Controller::content_shown(app);
             }))
             .unwrap();
     }
@@ -1340,13 +1681,38 @@ impl Controller {
             .unwrap();
     }
 
+    /// Sends a command to a CSO phone-book server and shows the
+    /// formatted response in the CSO dialog.
+    pub fn query_cso(&self, url: Url, command: String) {
+        self.set_message("Querying CSO server ...");
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let result = match crate::cso::request(&url, &command) {
+                Ok(lines) if command == "fields" => crate::cso::format_fields(&lines),
+                Ok(lines) => crate::cso::format_records(&lines),
+                Err(err) => err,
+            };
+            sender
+                .send(Box::new(move |app| {
+                    app.call_on_name("cso_result", |view: &mut TextView| {
+                        view.set_content(result);
+                    });
+                    let controller = app.user_data::<Controller>().expect("controller missing");
+                    controller.set_message(url.as_str());
+                }))
+                .unwrap();
+        });
+    }
+
     fn open_gemini_address(&mut self, url: Url, index: usize) {
         self.set_message("Loading ...");
+        self.loading = true;
         self.fetch_gemini_url(url, index);
     }
 
     fn open_finger_address(&mut self, url: Url, index: usize) {
         self.set_message("Loading ...");
+        self.loading = true;
         self.fetch_finger_url(url, index);
     }
 
@@ -1358,6 +1724,7 @@ impl Controller {
         index: usize,
         cert_fingerprint: Option<String>,
     ) {
+        self.loading = false;
         let mut guard = self.content.lock().unwrap();
         guard.clear();
         guard.push_str(content.as_str());
@@ -1375,7 +1742,15 @@ impl Controller {
             }
         }
 
+        let title = match gemini_type {
+            GeminiType::Gemini | GeminiType::Markdown => crate::gemini::title(&content),
+            _ => None,
+        };
+        self.set_page_title(title);
+
         let human_url = human_readable_url(&url);
+        self.gemini_page = (url, gemini_type);
+        self.preformatted.clear();
         // ensure gemini view is focused before setting content
         self.sender
             .send(Box::new(move |app| {
@@ -1392,6 +1767,12 @@ impl Controller {
             }))
             .unwrap();
 
+        self.render_gemini_content(index);
+    }
+
+    /// Renders the current content into the gemini view and selects
+    /// the row `index`
+    fn render_gemini_content(&self, index: usize) {
         self.sender
             .send(Box::new(move |app| {
                 let textwrap = SETTINGS
@@ -1408,44 +1789,176 @@ impl Controller {
 
                 let viewport_width = std::cmp::min(textwrap, viewport_width);
 
+                let controller = app.user_data::<Controller>().expect("controller missing");
+                let (url, gemini_type) = controller.gemini_page.clone();
+                let content = controller.content.lock().unwrap().clone();
+                let mut blocks = std::mem::take(&mut controller.preformatted);
+                let history = controller.history.clone();
+                let history = history.lock().unwrap();
+                let visited = |url: &Url| history.is_visited(url);
+
                 let mut view = app
                     .find_name::<SelectView<Option<Url>>>("gemini_content")
                     .expect("gemini content view missing");
                 view.clear();
 
-                if gemini_type == GeminiType::Text {
-                    let content = str::replace(&content, "\t", "        ");
-                    view.add_all(
-                        LinesIterator::new(&content, viewport_width)
-                            .map(|row| (&content[row.start..row.end], None))
-                            .collect::<Vec<_>>(),
-                    );
-                } else {
-                    view.add_all(crate::gemini::parse(&content, &url, viewport_width));
+                // XML documents that are no feeds are shown as text
+                let feed = match gemini_type {
+                    GeminiType::Feed => crate::feeds::parse_xml(&content, &url),
+                    _ => None,
+                };
+                let gemini_type = match gemini_type {
+                    GeminiType::Feed if feed.is_none() => GeminiType::Text,
+                    other => other,
+                };
+
+                match gemini_type {
+                    GeminiType::Text => {
+                        let content = str::replace(&content, "\t", "        ");
+                        if crate::ansi::has_escapes(&content) {
+                            let styled = crate::ansi::render(&content);
+                            view.add_all(
+                                crate::ansi::wrap(&styled, viewport_width)
+                                    .into_iter()
+                                    .map(|row| (row, None)),
+                            );
+                        } else {
+                            view.add_all(
+                                LinesIterator::new(&content, viewport_width)
+                                    .map(|row| (&content[row.start..row.end], None))
+                                    .collect::<Vec<_>>(),
+                            );
+                        }
+                    }
+                    GeminiType::Gemini => {
+                        view.add_all(crate::gemini::parse(
+                            &content,
+                            &url,
+                            viewport_width,
+                            &visited,
+                            &mut blocks,
+                        ));
+                    }
+                    GeminiType::Html => {
+                        let nodes = crate::html::parse(&content);
+                        view.add_all(crate::gemini::render(
+                            nodes,
+                            &[],
+                            &url,
+                            viewport_width,
+                            &visited,
+                            &mut blocks,
+                        ));
+                    }
+                    GeminiType::Markdown => {
+                        let (nodes, alt_texts) = crate::markdown::parse(&content);
+                        view.add_all(crate::gemini::render(
+                            nodes,
+                            &alt_texts,
+                            &url,
+                            viewport_width,
+                            &visited,
+                            &mut blocks,
+                        ));
+                    }
+                    GeminiType::Feed => {
+                        let nodes = feed.map(|feed| feed.nodes()).unwrap_or_default();
+                        view.add_all(crate::gemini::render(
+                            nodes,
+                            &[],
+                            &url,
+                            viewport_width,
+                            &visited,
+                            &mut blocks,
+                        ));
+                    }
                 }
                 view.set_on_submit(|app, _entry| {
                     let view = app
                         .find_name::<SelectView<Option<Url>>>("gemini_content")
                         .expect("gemini content view missing");
                     if let Some(selected_id) = view.selected_id() {
+                        let controller = app.user_data::<Controller>().expect("controller missing");
+                        if controller.toggle_preformatted(selected_id) {
+                            return;
+                        }
                         if let Some((label, entry)) = view.get_item(selected_id) {
                             if let Some(url) = entry {
-                                app.user_data::<Controller>()
-                                    .expect("controller missing")
-                                    .open_url(url.clone(), true, 0)
+                                controller.open_url(url.clone(), true, 0)
                             } else {
-                                let controller =
-                                    app.user_data::<Controller>().expect("controller missing");
                                 controller.open_link_in_label(label.to_string());
                             }
                         }
                     }
                 });
                 view.set_selection(index);
+                drop(view);
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .preformatted = blocks;
+                Controller::content_shown(app);
             }))
             .unwrap();
     }
 
+    /// Lays out the content that was just shown. Scrolls to the row
+    /// saved in the reading list and runs the scroll callback, so a page
+    /// that fits on the screen and never scrolls is read as well.
+    fn content_shown(app: &mut Cursive) {
+        app.call_on_name("main", |v: &mut Layout| v.relayout());
+        let top = app
+            .user_data::<Controller>()
+            .expect("controller missing")
+            .scroll_to
+            .take();
+        let result = if let Some(mut scroll) = app.find_name::<ScrollView<
+            ResizedView<NamedView<SelectView<GopherMapEntry>>>,
+        >>("content_scroll")
+        {
+            let top = top.unwrap_or_else(|| scroll.content_viewport().top());
+            scroll.set_offset((0, top))
+        } else if let Some(mut scroll) = app.find_name::<ScrollView<
+            ResizedView<NamedView<SelectView<Option<Url>>>>,
+        >>("gemini_content_scroll")
+        {
+            let top = top.unwrap_or_else(|| scroll.content_viewport().top());
+            scroll.set_offset((0, top))
+        } else {
+            unreachable!("view content_scroll and gemini_content_scroll missing");
+        };
+        result.process(app);
+    }
+
+    /// Folds or unfolds the preformatted block with its caption in the
+    /// given row of the gemini view. Returns false if there is none.
+    pub fn toggle_preformatted(&mut self, row: usize) -> bool {
+        match self
+            .preformatted
+            .iter_mut()
+            .find(|block| !block.alt.is_empty() && block.rows.start == row)
+        {
+            Some(block) => {
+                block.folded = !block.folded;
+                self.render_gemini_content(row);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scrolls the preformatted block in the given row of the gemini
+    /// view horizontally
+    pub fn scroll_preformatted(&mut self, row: usize, columns: isize) {
+        if let Some(block) = self
+            .preformatted
+            .iter_mut()
+            .find(|block| block.rows.contains(&row))
+        {
+            block.offset = block.offset.saturating_add_signed(columns);
+            self.render_gemini_content(row);
+        }
+    }
+
     /// Renders finger content
     fn set_finger_content(&mut self, url: Url, content: String, index: usize) {
         let mut guard = self.content.lock().unwrap();
@@ -1453,8 +1966,19 @@ impl Controller {
         guard.push_str(content.as_str());
         drop(guard);
 
+        let user = match url.username() {
+            "" => url.path().trim_matches('/'),
+            username => username,
+        };
+        let host = url.host_str().unwrap_or_default();
+        let title = match user {
+            "" => host.to_string(),
+            user => format!("{}@{}", user, host),
+        };
+
         self.clear_search();
         self.set_gemini_content(url, GeminiType::Text, content, index, None);
+        self.set_page_title(Some(title));
     }
 
     fn add_to_history(&mut self, url: Url, index: usize) {
@@ -1474,12 +1998,12 @@ impl Controller {
                 let idx = Controller::get_selected_item_index(app);
                 let controller = app.user_data::<Controller>().expect("controller missing");
                 let mut guard = controller.history.lock().unwrap();
-                guard.update_selected_item(idx);
+                guard.session.set_position(idx);
                 drop(guard);
                 info!("add_to_history(): {}", url);
                 let h = HistoryEntry {
                     title: url.to_string(),
-                    url: url.clone(),
+                    url,
                     timestamp: OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc()),
                     visited_count: 1,
                     position: 0,
@@ -1488,32 +2012,56 @@ impl Controller {
                     .history
                     .lock()
                     .unwrap()
-                    .add(h.clone())
+                    .add(h)
                     .expect("Could not add to history");
-                let menu = app
-                    .menubar()
-                    .find_subtree("History")
-                    .expect("history menu missing");
-                if let Some(idx) = menu.find_position(url.as_ref()) {
-                    if idx >= 3 {
-                        menu.remove(idx);
-                    }
-                }
-                // Add 3 for the two first menuitems + separator
-                if menu.len() > HISTORY_LEN + 3 {
-                    menu.remove(menu.len() - 1);
+                controller.update_history_menu();
+            }))
+            .unwrap();
+    }
+
+    /// Shows the latest history entries in the history menu
+    fn update_history_menu(&self) {
+        let entries = match self.history.lock().unwrap().get_latest_history(HISTORY_LEN) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Could not get latest history: {}", err);
+                return;
+            }
+        };
+        self.sender
+            .send(Box::new(move |app| {
+                crate::ui::setup::setup_history_menu(app, &entries);
+            }))
+            .unwrap();
+    }
+
+    /// Remembers the title of the current page and stores it in the
+    /// history. Pages without title keep the title stored before.
+    fn set_page_title(&mut self, title: Option<String>) {
+        self.current_title = title.clone();
+        self.update_tab_bar();
+        let Some(title) = title else {
+            return;
+        };
+        if SETTINGS.read().unwrap().config.disable_history {
+            return;
+        }
+        let url = self.current_url.lock().unwrap().clone();
+        // The page is added to the history in a callback, too
+        self.sender
+            .send(Box::new(move |app| {
+                let controller = app.user_data::<Controller>().expect("controller missing");
+                let result = controller.history.lock().unwrap().set_title(&url, &title);
+                match result {
+                    Ok(true) => controller.update_history_menu(),
+                    Ok(false) => (),
+                    Err(err) => warn!("Could not store title of {}: {}", url, err),
                 }
-                menu.insert_leaf(3, h.title, move |app| {
-                    app.user_data::<Controller>()
-                        .expect("controller missing")
-                        .open_url(url.clone(), true, 0);
-                });
             }))
             .unwrap();
     }
 
     /// Purges the entire history
-    /// TODO: Add option to clear only parts of the history
     pub fn clear_history(&mut self) {
         // Purge file
         self.history
@@ -1528,35 +2076,785 @@ impl Controller {
                     .menubar()
                     .find_subtree("History")
                     .expect("history menu missing");
-                // remove everything but the first three elements
-                while menu.len() > 3 {
-                    menu.remove(3);
+                // remove everything but the fixed menu items
+                while menu.len() > HISTORY_MENU_ITEMS {
+                    menu.remove(HISTORY_MENU_ITEMS);
                 }
             }))
             .unwrap();
     }
 
-    /// Navigates to the previous page in history
-    pub fn navigate_back(&mut self) {
-        let mut guard = self.history.lock().unwrap();
-        let history = guard.back();
-        if let Some(h) = history {
-            drop(guard);
-            info!("NAVIGATE_BACK to index {}", h.position);
-            self.open_url(h.url, false, h.position);
+    /// Purges the history entries last visited more than `hours` ago
+    pub fn clear_history_older_than(&mut self, hours: u32) {
+        let result = self.history.lock().unwrap().clear_older_than(hours);
+        match result {
+            Ok(removed) => {
+                self.set_message(&format!("Removed {} history entries", removed));
+                self.update_history_menu();
+            }
+            Err(err) => self.set_message(&format!("Could not clear history: {}", err)),
         }
     }
 
-    fn open_command(&mut self, command: &str, url: Url) -> Result<(), Box<dyn Error>> {
-        // Opens a URL in an external application - if defined in settings
-        let command = match command {
-            "html_command" => SETTINGS.read().unwrap().config.html_command.clone(),
-            "image_command" => SETTINGS.read().unwrap().config.image_command.clone(),
-            "telnet_command" => SETTINGS.read().unwrap().config.telnet_command.clone(),
-            _ => panic!("unknown field"),
+    /// Subscribes to the current gemini page or Atom/RSS feed. The link
+    /// lines of gemini pages starting with a date are the entries of
+    /// the feed.
+    pub fn subscribe_current_page(&mut self) {
+        let url = self.current_url.lock().unwrap().clone();
+        let content = self.content.lock().unwrap().clone();
+        let feed = match self.gemini_page.1 {
+            GeminiType::Gemini if url.scheme() == "gemini" => {
+                Some(crate::feeds::parse_gemini(&content, &url))
+            }
+            GeminiType::Feed => crate::feeds::parse_xml(&content, &url),
+            _ => None,
+        };
+        let Some(feed) = feed else {
+            self.set_message("Only gemini pages and Atom or RSS feeds can be subscribed to");
+            return;
         };
+        if feed.entries.is_empty() {
+            self.set_message("This page has no dated links to subscribe to");
+            return;
+        }
+        let mut feeds = self.feeds.lock().unwrap();
+        if feeds.get_subscription(&url).is_some() {
+            drop(feeds);
+            self.set_message(&format!("Already subscribed to {}", url));
+            return;
+        }
+        let title = feed
+            .title
+            .clone()
+            .unwrap_or_else(|| human_readable_url(&url));
+        // The entries that are already on the page are not new
+        let result = feeds
+            .subscribe(SourceKind::Feed, &url, &title)
+            .and_then(|subscription| feeds.update(&subscription, &feed, true));
+        drop(feeds);
+        match result {
+            Ok(_) => self.set_message(&format!("Subscribed to {}", title)),
+            Err(err) => self.set_message(&format!("Could not subscribe: {}", err)),
+        }
+    }
 
-        if !command.is_empty() {
+    /// Fetches all subscriptions and watched gophermaps in the
+    /// background, unless the last update is still running
+    pub fn update_feeds(&mut self) {
+        if self.updating_feeds {
+            info!("Skipping feed update, the last update is still running");
+            return;
+        }
+        let subscriptions = match self.feeds.lock().unwrap().subscriptions() {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => {
+                warn!("Could not read subscriptions: {}", err);
+                return;
+            }
+        };
+        self.updating_feeds = true;
+        let certificates = self.certificates.clone();
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for subscription in subscriptions {
+                let feed = Controller::fetch_feed(&subscription, &certificates);
+                let sent = sender.send(Box::new(move |app| {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .feed_fetched(subscription, feed);
+                }));
+                // the application was closed
+                if sent.is_err() {
+                    return;
+                }
+            }
+            sender
+                .send(Box::new(|app| {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .updating_feeds = false;
+                }))
+                .ok();
+        });
+    }
+
+    fn fetch_feed(
+        subscription: &Subscription,
+        certificates: &Arc<Mutex<Certificates>>,
+    ) -> Result<Feed, String> {
+        let url = &subscription.url;
+        if subscription.kind == SourceKind::Phlog {
+            let body = Controller::gopher_fetch(url)?;
+            return Ok(crate::phlogs::parse_gophermap(&String::from_utf8_lossy(
+                &body,
+            )));
+        }
+        let (mime, body) = match url.scheme() {
+            "gemini" => Controller::gemini_request(url.clone(), certificates)?,
+            // Gopher has no MIME types, feeds are always XML
+            "gopher" => (
+                "application/xml".to_string(),
+                Controller::gopher_fetch(url)?,
+            ),
+            scheme => return Err(format!("unsupported scheme {}", scheme)),
+        };
+        let text = String::from_utf8_lossy(&body);
+        if mime.starts_with("text/gemini") {
+            Ok(crate::feeds::parse_gemini(&text, url))
+        } else {
+            crate::feeds::parse_xml(&text, url).ok_or_else(|| format!("{} is no feed", mime))
+        }
+    }
+
+    /// Requests a gopher URL without user interaction and returns the
+    /// raw response
+    fn gopher_fetch(url: &Url) -> Result<Vec<u8>, String> {
+        let binary = decode_binary(url.path().as_bytes());
+        let path = String::from_utf8_lossy(&binary)
+            .get(2..)
+            .unwrap_or_default()
+            .to_string();
+        let server = url.host_str().ok_or("no host")?;
+        let config = Controller::get_tls_client_config(&None, &None);
+        let timeout = Duration::from_secs(REQUEST_TIMEOUT);
+        Controller::gopher_request(server, url.port().unwrap_or(70), &path, config, timeout)
+            .map_err(|err| err.to_string())
+    }
+
+    fn feed_fetched(&mut self, subscription: Subscription, feed: Result<Feed, String>) {
+        let feed = match feed {
+            Ok(feed) => feed,
+            Err(err) => {
+                warn!("Could not update feed {}: {}", subscription.url, err);
+                return;
+            }
+        };
+        let result = self
+            .feeds
+            .lock()
+            .unwrap()
+            .update(&subscription, &feed, false);
+        match result {
+            Ok(0) => (),
+            Ok(new_entries) => self.set_message(&format!(
+                "{} new entries in {}, see {}",
+                new_entries,
+                subscription.title,
+                subscription.kind.page_url()
+            )),
+            Err(err) => warn!("Could not store feed {}: {}", subscription.url, err),
+        }
+    }
+
+    /// Watches the current gophermap for new items
+    pub fn watch_current_gophermap(&mut self) {
+        let url = self.current_url.lock().unwrap().clone();
+        if url.scheme() != "gopher" || !ItemType::from_url(&url).is_dir() {
+            self.set_message("Only gophermaps can be watched");
+            return;
+        }
+        let mut feeds = self.feeds.lock().unwrap();
+        if feeds.get_subscription(&url).is_some() {
+            drop(feeds);
+            self.set_message(&format!("Already watching {}", url));
+            return;
+        }
+        let title = human_readable_url(&url);
+        let feed = crate::phlogs::parse_gophermap(&self.content.lock().unwrap());
+        // The items that are already in the gophermap are not new
+        let result = feeds
+            .subscribe(SourceKind::Phlog, &url, &title)
+            .and_then(|subscription| feeds.update(&subscription, &feed, true));
+        drop(feeds);
+        match result {
+            Ok(_) => self.set_message(&format!("Watching {}", title)),
+            Err(err) => self.set_message(&format!("Could not watch gophermap: {}", err)),
+        }
+    }
+
+    /// Items of a watched gophermap that is shown are not new anymore
+    fn gophermap_fetched(&mut self, url: &Url, content: &str) {
+        let mut feeds = self.feeds.lock().unwrap();
+        let subscription = feeds
+            .get_subscription(url)
+            .filter(|s| s.kind == SourceKind::Phlog);
+        if let Some(subscription) = subscription {
+            let feed = crate::phlogs::parse_gophermap(content);
+            if let Err(err) = feeds.update(&subscription, &feed, true) {
+                warn!("Could not update phlog {}: {}", url, err);
+            }
+        }
+    }
+
+    /// Adds the current page to the reading list. The page is scrolled
+    /// to the row `top` when it is opened from the reading list.
+    pub fn add_to_reading_list(&mut self, top: usize) {
+        let url = self.current_url.lock().unwrap().clone();
+        if !matches!(url.scheme(), "gopher" | "gemini" | "finger") {
+            self.set_message(
+                "Only gopher, gemini and finger pages can be added to the reading list",
+            );
+            return;
+        }
+        let copy = if SETTINGS.read().unwrap().config.reading_list_offline {
+            Some(self.current_page())
+        } else {
+            None
+        };
+        let title = self
+            .current_title
+            .clone()
+            .unwrap_or_else(|| human_readable_url(&url));
+        let result = self
+            .reading_list
+            .lock()
+            .unwrap()
+            .add(&url, &title, top, copy);
+        match result {
+            Ok(true) => self.set_message(&format!("Added {} to the reading list", title)),
+            Ok(false) => self.set_message(&format!("Updated {} in the reading list", title)),
+            Err(err) => self.set_message(&format!("Could not add to the reading list: {}", err)),
+        }
+    }
+
+    /// Returns a copy of the current page that can be shown again with
+    /// `show_page`
+    fn current_page(&self) -> CachedPage {
+        let url = self.current_url.lock().unwrap().clone();
+        let item_type = ItemType::from_url(&url);
+        let kind = if url.scheme() == "gopher" && !item_type.is_text() && !item_type.is_html() {
+            "gophermap"
+        } else {
+            self.gemini_page.1.name()
+        };
+        CachedPage {
+            kind: kind.to_string(),
+            content: self.content.lock().unwrap().clone(),
+        }
+    }
+
+    /// Shows a stored copy of a page and selects the row `index`
+    fn show_page(&mut self, url: Url, page: CachedPage, index: usize) {
+        *self.current_url.lock().unwrap() = url.clone();
+        self.clear_search();
+        match GeminiType::from_name(&page.kind) {
+            Some(gemini_type) => {
+                self.set_gemini_content(url, gemini_type, page.content, index, None)
+            }
+            None => self.set_gopher_content(ItemType::from_url(&url), page.content, index),
+        }
+    }
+
+    /// Renders the current page again, e.g. after the settings changed
+    pub fn redraw_page(&mut self, index: usize) {
+        let url = self.current_url.lock().unwrap().clone();
+        let page = self.current_page();
+        self.show_page(url, page, index);
+    }
+
+    /// Opens an item of the reading list at its saved position, either
+    /// from the network or from the stored copy
+    pub fn open_reading_list_item(&mut self, item: &ReadingListItem, offline: bool) {
+        if !offline {
+            self.open_url(item.url.clone(), true, item.position);
+            self.scroll_to = Some(item.position);
+            return;
+        }
+        let cached = match self.reading_list.lock().unwrap().cached_page(item.id) {
+            Ok(Some(cached)) => cached,
+            Ok(None) => {
+                self.set_message("No offline copy of this page was kept");
+                return;
+            }
+            Err(err) => {
+                self.set_message(&format!("Could not read offline copy: {}", err));
+                return;
+            }
+        };
+        let url = item.url.clone();
+        self.add_to_history(url.clone(), item.position);
+        self.set_message(&format!("{} (offline copy)", url));
+        self.scroll_to = Some(item.position);
+        self.show_page(url, cached, item.position);
+    }
+
+    /// Marks the current page as read if it is in the reading list.
+    /// Called whenever the end of the page is visible.
+    pub fn reached_end_of_page(&mut self) {
+        let url = self.current_url.lock().unwrap().clone();
+        let result = self.reading_list.lock().unwrap().mark_read(&url);
+        match result {
+            Ok(true) => self.set_message(&format!(
+                "Finished reading {}, marked as read",
+                human_readable_url(&url)
+            )),
+            Ok(false) => (),
+            Err(err) => warn!("Could not mark reading list item as read: {}", err),
+        }
+    }
+
+    /// Requests a gemini URL without user interaction, following
+    /// redirects. Returns the MIME type and body of a successful
+    /// response. Hosts with a changed certificate are not contacted.
+    fn gemini_request(
+        mut url: Url,
+        certificates: &Arc<Mutex<Certificates>>,
+    ) -> Result<(String, Vec<u8>), String> {
+        for _ in 0..MAX_REDIRECTS {
+            normalize_domain(&mut url);
+            let host = url.host_str().ok_or("no host")?.to_string();
+            let server_details = url
+                .socket_addrs(|| Some(1965))
+                .map_err(|err| err.to_string())?
+                .first()
+                .copied()
+                .ok_or("could not resolve host")?;
+            let config = Controller::get_tls_client_config(&None, &None);
+            let server_name = host.clone().try_into().map_err(|_| "invalid host name")?;
+            let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name)
+                .map_err(|err| err.to_string())?;
+            let timeout = Duration::from_secs(REQUEST_TIMEOUT);
+            let mut stream = TcpStream::connect_timeout(&server_details, timeout)
+                .map_err(|err| err.to_string())?;
+            stream
+                .set_read_timeout(Some(timeout))
+                .and_then(|()| stream.set_write_timeout(Some(timeout)))
+                .map_err(|err| err.to_string())?;
+            // The certificate is checked before the request reveals
+            // the URL
+            conn.complete_io(&mut stream)
+                .map_err(|err| err.to_string())?;
+            let known = certificates.lock().unwrap().get(&url);
+            let cert = conn.peer_certificates().and_then(|certs| certs.first());
+            if let (Some(known), Some(cert)) = (known, cert) {
+                let hash = ring::digest::digest(&ring::digest::SHA256, cert);
+                if general_purpose::STANDARD.encode(hash) != known {
+                    return Err(format!("certificate of {} has changed", host));
+                }
+            }
+
+            let mut tls = rustls::Stream::new(&mut conn, &mut stream);
+            tls.write_all(format!("{}\r\n", url).as_bytes())
+                .map_err(|err| err.to_string())?;
+
+            let mut bufr = BufReader::new(tls);
+            let mut header = String::new();
+            bufr.read_line(&mut header).map_err(|err| err.to_string())?;
+            let meta = header.get(3..).unwrap_or_default().trim().to_string();
+            match header.chars().next() {
+                Some('2') => {
+                    let mut body = vec![];
+                    // Some servers close the connection without TLS close_notify
+                    if let Err(err) = bufr.take(MAX_BODY_LEN + 1).read_to_end(&mut body) {
+                        if err.kind() != std::io::ErrorKind::UnexpectedEof {
+                            return Err(err.to_string());
+                        }
+                    }
+                    if body.len() as u64 > MAX_BODY_LEN {
+                        return Err(format!("response is larger than {} bytes", MAX_BODY_LEN));
+                    }
+                    return Ok((meta, body));
+                }
+                Some('3') => {
+                    url = url.join(&meta).map_err(|err| err.to_string())?;
+                    if url.scheme() != "gemini" {
+                        return Err(format!("redirect to non-gemini URL {}", url));
+                    }
+                }
+                _ => return Err(format!("server responded {}", header.trim())),
+            }
+        }
+        Err("too many redirects".to_string())
+    }
+
+    /// Navigates to the previous page in history. `index` is the
+    /// selected row of the current page, it is restored when navigating
+    /// forward again.
+    pub fn navigate_back(&mut self, index: usize) {
+        let mut guard = self.history.lock().unwrap();
+        guard.session.set_position(index);
+        let history = guard.session.back();
+        if let Some(h) = history {
+            drop(guard);
+            info!("NAVIGATE_BACK to index {}", h.position);
+            self.open_url(h.url, false, h.position);
+        }
+    }
+
+    /// Navigates to the page that was left by navigating back
+    pub fn navigate_forward(&mut self, index: usize) {
+        let mut guard = self.history.lock().unwrap();
+        guard.session.set_position(index);
+        let history = guard.session.forward();
+        if let Some(h) = history {
+            drop(guard);
+            info!("NAVIGATE_FORWARD to index {}", h.position);
+            self.open_url(h.url, false, h.position);
+        } else {
+            drop(guard);
+            self.set_message("No page to go forward to");
+        }
+    }
+
+    /// Navigates to any page of the session tree
+    pub fn navigate_to(&mut self, index: usize, id: usize) {
+        let mut guard = self.history.lock().unwrap();
+        guard.session.set_position(index);
+        let history = guard.session.jump(id);
+        if let Some(h) = history {
+            drop(guard);
+            info!("NAVIGATE_TO {} index {}", h.url, h.position);
+            self.open_url(h.url, false, h.position);
+        }
+    }
+
+    /// Opens the URL in a new tab next to the current one. Gopher and
+    /// gemini pages are loaded in the background while the current tab
+    /// stays visible.
+    pub fn open_in_new_tab(&mut self, url: Url) {
+        if !matches!(url.scheme(), "gopher" | "gemini" | "finger" | "about") {
+            self.set_message(&format!("Cannot open {} in a tab", url));
+            return;
+        }
+        let mut tab = Tab::new(self.next_tab_id, url.clone());
+        self.next_tab_id += 1;
+        if !SETTINGS.read().unwrap().config.disable_history {
+            tab.session.push(HistoryEntry {
+                title: url.to_string(),
+                url: url.clone(),
+                timestamp: OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc()),
+                visited_count: 1,
+                position: 0,
+            });
+            if let Err(err) = self.history.lock().unwrap().record(&url) {
+                warn!("Could not add {} to history: {}", url, err);
+            }
+            self.update_history_menu();
+        }
+        if matches!(url.scheme(), "gopher" | "gemini") {
+            tab.loading = true;
+            let id = tab.id;
+            let url = url.clone();
+            let certificates = self.certificates.clone();
+            let sender = self.sender.clone();
+            thread::spawn(move || {
+                let page = Controller::fetch_page(&url, &certificates);
+                let sent = sender.send(Box::new(move |app| {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .tab_loaded(id, page);
+                }));
+                if sent.is_err() {
+                    warn!("Could not deliver {}, the application was closed", url);
+                }
+            });
+        }
+        self.tabs.insert(self.current_tab + 1, tab);
+        self.update_tab_bar();
+        self.set_message(&format!("Opened {} in a new tab", human_readable_url(&url)));
+    }
+
+    /// Requests a page without user interaction so it can be shown in
+    /// a background tab. Like feeds, gemini pages are only requested
+    /// from hosts whose certificate did not change.
+    fn fetch_page(
+        url: &Url,
+        certificates: &Arc<Mutex<Certificates>>,
+    ) -> Result<CachedPage, String> {
+        match url.scheme() {
+            "gemini" => {
+                let (meta, body) = Controller::gemini_request(url.clone(), certificates)?;
+                let mime = meta
+                    .parse::<Mime>()
+                    .unwrap_or_else(|_| "text/gemini".parse().unwrap());
+                if mime.type_() != "text" && !crate::feeds::is_xml_feed(&mime) {
+                    return Err(format!("{} is not loaded in the background", mime));
+                }
+                Ok(CachedPage {
+                    kind: Controller::gemini_type(&mime).name().to_string(),
+                    content: String::from_utf8_lossy(&body).into_owned(),
+                })
+            }
+            "gopher" => {
+                let item_type = ItemType::from_url(url);
+                let kind = if item_type.is_dir() {
+                    "gophermap"
+                } else if item_type.is_text() || item_type.is_html() {
+                    "gopher"
+                } else {
+                    return Err("only gophermaps and text are loaded in the background".into());
+                };
+                let body = Controller::gopher_fetch(url)?;
+                Ok(CachedPage {
+                    kind: kind.to_string(),
+                    content: String::from_utf8_lossy(&body).into_owned(),
+                })
+            }
+            scheme => Err(format!("unsupported scheme {}", scheme)),
+        }
+    }
+
+    /// Stores a page loaded in the background in its tab
+    fn tab_loaded(&mut self, id: usize, page: Result<CachedPage, String>) {
+        // The tab may have been closed or shown in the meantime, shown
+        // tabs request their page themselves
+        let tab = match self.tabs.iter_mut().find(|tab| tab.id == id) {
+            Some(tab) if tab.loading => tab,
+            _ => return,
+        };
+        tab.loading = false;
+        match page {
+            Ok(page) => {
+                tab.title = crate::tabs::title(&page);
+                tab.page = Some(page);
+            }
+            // the page is requested again once the tab is shown
+            Err(err) => {
+                warn!("Could not load {} in the background: {}", tab.url, err);
+                let message = format!(
+                    "Could not load {} in a new tab: {}",
+                    human_readable_url(&tab.url),
+                    err
+                );
+                self.set_message(&message);
+            }
+        }
+        self.update_tab_bar();
+    }
+
+    /// Shows the next or previous tab. `index` is the selected row of
+    /// the current tab.
+    pub fn cycle_tabs(&mut self, forward: bool, index: usize) {
+        let len = self.tabs.len();
+        if len == 1 {
+            self.set_message("There are no other tabs");
+            return;
+        }
+        let next = if forward {
+            (self.current_tab + 1) % len
+        } else {
+            (self.current_tab + len - 1) % len
+        };
+        self.store_current_tab(index);
+        self.show_tab(next);
+    }
+
+    /// Closes the current tab and shows its neighbour
+    pub fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            self.set_message("Cannot close the last tab");
+            return;
+        }
+        // a pending request of the closed tab must not show its page
+        *self.last_request_id.lock().unwrap() += 1;
+        self.tabs.remove(self.current_tab);
+        self.show_tab(self.current_tab.min(self.tabs.len() - 1));
+    }
+
+    /// Keeps the state of the current tab while another tab is shown
+    fn store_current_tab(&mut self, index: usize) {
+        // a pending request would show its page in the wrong tab, the
+        // page is requested again when the tab is shown
+        *self.last_request_id.lock().unwrap() += 1;
+        let page = if self.loading {
+            None
+        } else {
+            Some(self.current_page())
+        };
+        let session = std::mem::take(&mut self.history.lock().unwrap().session);
+        let tab = &mut self.tabs[self.current_tab];
+        tab.url = self.current_url.lock().unwrap().clone();
+        tab.title = self.current_title.clone();
+        tab.page = page;
+        tab.position = index;
+        tab.session = session;
+        tab.search = if self.current_search_results.is_empty() {
+            String::new()
+        } else {
+            self.current_search.clone()
+        };
+    }
+
+    /// Makes the tab with the index the current tab and shows its page
+    fn show_tab(&mut self, index: usize) {
+        self.current_tab = index;
+        let tab = &mut self.tabs[index];
+        // a page that is still loading in the background is requested
+        // again, so errors and prompts are shown
+        tab.loading = false;
+        let url = tab.url.clone();
+        let position = tab.position;
+        let page = tab.page.take();
+        let search = std::mem::take(&mut tab.search);
+        self.history.lock().unwrap().session = std::mem::take(&mut tab.session);
+        self.current_title = tab.title.take();
+        self.loading = false;
+        match page {
+            Some(page) => {
+                self.set_message(url.as_str());
+                self.show_page(url, page, position);
+            }
+            None => self.open_url(url, false, position),
+        }
+        if !search.is_empty() {
+            self.highlight_search(search, false);
+        }
+        self.update_tab_bar();
+    }
+
+    /// Returns the open tabs with their back and forward history.
+    /// `index` is the selected row of the current tab, if it is not
+    /// known the row the page was opened at is used.
+    fn saved_session(&self, index: Option<usize>) -> SavedSession {
+        let saved_tab = |url: &Url, position: Option<usize>, session: &Session| {
+            let (entries, current) = session.branch();
+            let position = position
+                .or_else(|| entries.get(current).map(|e| e.position))
+                .unwrap_or(0);
+            SavedTab {
+                url: url.clone(),
+                position,
+                current,
+                pages: entries
+                    .into_iter()
+                    .map(|entry| SavedPage {
+                        url: entry.url,
+                        title: entry.title,
+                        position: entry.position,
+                    })
+                    .collect(),
+            }
+        };
+        let history = self.history.lock().unwrap();
+        let tabs = self
+            .tabs
+            .iter()
+            .enumerate()
+            .map(|(i, tab)| {
+                if i == self.current_tab {
+                    let url = self.current_url.lock().unwrap().clone();
+                    saved_tab(&url, index, &history.session)
+                } else {
+                    saved_tab(&tab.url, Some(tab.position), &tab.session)
+                }
+            })
+            .collect();
+        SavedSession {
+            current_tab: self.current_tab,
+            tabs,
+        }
+    }
+
+    /// Returns true if the open tabs are saved
+    fn saves_session() -> bool {
+        let config = &SETTINGS.read().unwrap().config;
+        config.save_session && !config.disable_history
+    }
+
+    /// Keeps a snapshot of the open tabs for the panic hook
+    fn update_snapshot(&self, index: Option<usize>) {
+        if Controller::saves_session() {
+            self.saved_session(index).set_snapshot();
+        }
+    }
+
+    /// Writes a snapshot of the open tabs to disk
+    fn write_snapshot(app: &mut Cursive) {
+        let index = Controller::get_selected_item_index(app);
+        let controller = app.user_data::<Controller>().expect("controller missing");
+        if !Controller::saves_session() {
+            return;
+        }
+        controller.update_snapshot(Some(index));
+        if let Err(err) = crate::session::write_snapshot() {
+            warn!("Could not write session snapshot: {}", err);
+        }
+    }
+
+    /// Saves the open tabs when ncgopher exits
+    pub fn save_session(app: &mut Cursive) {
+        if !Controller::saves_session() {
+            crate::session::remove();
+            return;
+        }
+        let index = Controller::get_selected_item_index(app);
+        let controller = app.user_data::<Controller>().expect("controller missing");
+        if let Err(err) = controller.saved_session(Some(index)).save() {
+            warn!("Could not save session: {}", err);
+        }
+    }
+
+    /// Replaces the open tabs with the tabs of a saved session
+    pub fn restore_session(&mut self, saved: SavedSession) {
+        if saved.tabs.is_empty() {
+            return;
+        }
+        // a pending request must not show its page in a restored tab
+        *self.last_request_id.lock().unwrap() += 1;
+        let timestamp = OffsetDateTime::now_local().unwrap_or(OffsetDateTime::now_utc());
+        let mut tabs = Vec::new();
+        for saved_tab in saved.tabs {
+            let mut tab = Tab::new(self.next_tab_id, saved_tab.url);
+            self.next_tab_id += 1;
+            tab.position = saved_tab.position;
+            let entries: Vec<HistoryEntry> = saved_tab
+                .pages
+                .into_iter()
+                .map(|page| HistoryEntry {
+                    title: page.title,
+                    url: page.url,
+                    timestamp,
+                    visited_count: 1,
+                    position: page.position,
+                })
+                .collect();
+            tab.title = entries
+                .get(saved_tab.current)
+                .filter(|entry| entry.title != entry.url.as_str())
+                .map(|entry| entry.title.clone());
+            tab.session = Session::from_branch(entries, saved_tab.current);
+            tabs.push(tab);
+        }
+        self.tabs = tabs;
+        self.show_tab(saved.current_tab.min(self.tabs.len() - 1));
+    }
+
+    /// Shows the labels of all tabs in the tab bar
+    fn update_tab_bar(&self) {
+        self.update_snapshot(None);
+        let current_url = self.current_url.lock().unwrap().clone();
+        let labels: Vec<String> = self
+            .tabs
+            .iter()
+            .enumerate()
+            .map(|(i, tab)| {
+                if i == self.current_tab {
+                    crate::tabs::label(self.current_title.as_deref(), &current_url, false)
+                } else {
+                    tab.label()
+                }
+            })
+            .collect();
+        let current = self.current_tab;
+        self.sender
+            .send(Box::new(move |app| {
+                app.find_name::<Layout>("main")
+                    .expect("main layout missing")
+                    .set_tabs(labels, current);
+            }))
+            .unwrap();
+    }
+
+    fn open_command(&mut self, command: &str, url: Url) -> Result<(), Box<dyn Error>> {
+        // Opens a URL in an external application - if defined in settings
+        let command = match command {
+            "html_command" => SETTINGS.read().unwrap().config.html_command.clone(),
+            "image_command" => SETTINGS.read().unwrap().config.image_command.clone(),
+            "telnet_command" => SETTINGS.read().unwrap().config.telnet_command.clone(),
+            _ => panic!("unknown field"),
+        };
+
+        if !command.is_empty() {
             if let Err(err) = Command::new(&command).arg(url.to_string()).spawn() {
                 self.set_message(&format!("Command failed: {}: {}", err, command));
             }
@@ -1566,6 +2864,94 @@ impl Controller {
         Ok(())
     }
 
+    /// Opens the current page with the configured HTML browser. HTML
+    /// pages that are not served via http(s) are passed as a local copy.
+    pub fn open_in_browser(&mut self) {
+        let url = self.current_url.lock().unwrap().clone();
+        if matches!(url.scheme(), "http" | "https") {
+            self.open_command("html_command", url).unwrap();
+            return;
+        }
+        if !self.is_html_page(&url) {
+            self.set_message("Only HTML pages can be opened in the external browser");
+            return;
+        }
+        let content = self.content.lock().unwrap().clone();
+        let written = Controller::create_temp_file("html").and_then(|(path, mut file)| {
+            file.write_all(content.as_bytes())?;
+            Ok(path)
+        });
+        let path = match written {
+            Ok(path) => path,
+            Err(err) => {
+                self.set_message(&format!("Unable to write temporary file: {}", err));
+                return;
+            }
+        };
+        match Url::from_file_path(&path) {
+            Ok(file_url) => self.open_command("html_command", file_url).unwrap(),
+            Err(_) => self.set_message(&format!("Invalid path {}", path.display())),
+        }
+    }
+
+    /// True if the page at `url` is HTML, served as text/html or as a
+    /// gopher item of type h
+    fn is_html_page(&self, url: &Url) -> bool {
+        match url.scheme() {
+            "gopher" => ItemType::from_url(url).is_html(),
+            _ => self.gemini_page == (url.clone(), GeminiType::Html),
+        }
+    }
+
+    /// Creates a new file with a unique name in the temporary
+    /// directory. Existing files, or links planted under the name,
+    /// are never opened.
+    fn create_temp_file(extension: &str) -> std::io::Result<(PathBuf, std::fs::File)> {
+        let dir = std::env::temp_dir();
+        for n in 0..100 {
+            let name = format!("ncgopher-{}-{}.{}", std::process::id(), n, extension);
+            let path = dir.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "no unused file name",
+        ))
+    }
+
+    /// Decodes a downloaded image in the background and shows a preview
+    fn preview_image_file(&self, url: Url, path: PathBuf) {
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let image = std::fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|data| imageview::decode(&data));
+            sender
+                .send(Box::new(move |app| match image {
+                    Ok(image) => crate::ui::dialogs::image_preview(app, url, image, Some(path)),
+                    Err(err) => {
+                        let controller = app.user_data::<Controller>().expect("controller missing");
+                        controller.set_message(&format!("Could not decode image: {}", err));
+                        controller.open_image_from_file(&path).ok();
+                    }
+                }))
+                .unwrap();
+        });
+    }
+
+    /// Opens an image in the images viewer, preferably from the
+    /// downloaded file
+    pub fn open_image_externally(&mut self, url: Url, path: Option<PathBuf>) {
+        match path {
+            Some(path) => self.open_image_from_file(&path).unwrap(),
+            None => self.open_command("image_command", url).unwrap(),
+        }
+    }
+
     fn open_image_from_file(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
         let command = SETTINGS.read().unwrap().config.image_command.clone();
         if !command.is_empty() {
@@ -1709,6 +3095,25 @@ impl Controller {
             .unwrap();
     }
 
+    /// Returns the first row of the content shown on the screen
+    pub fn get_scroll_offset(app: &mut Cursive) -> usize {
+        if let Some(scroll) = app
+            .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                "content_scroll",
+            )
+        {
+            scroll.content_viewport().top()
+        } else if let Some(scroll) = app
+            .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                "gemini_content_scroll",
+            )
+        {
+            scroll.content_viewport().top()
+        } else {
+            unreachable!("view content_scroll and gemini_content_scroll missing");
+        }
+    }
+
     pub fn get_selected_item_index(app: &mut Cursive) -> usize {
         if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
             content.selected_id()
@@ -1720,39 +3125,46 @@ impl Controller {
         .unwrap_or(0)
     }
 
-    pub fn add_bookmark_action(&mut self, url: Url, title: String, tags: String) {
-        let tags = tags.as_str().split_whitespace().map(String::from).collect();
-        let b = Bookmark { title, url, tags };
+    /// Returns the URL of the link in the selected row if it can be
+    /// opened in a tab
+    pub fn get_selected_link(app: &mut Cursive) -> Option<Url> {
+        if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
+            let (_, entry) = content.get_item(content.selected_id()?)?;
+            let openable = entry.item_type.is_dir()
+                || entry.item_type.is_text()
+                || (entry.item_type.is_html()
+                    && entry.url.scheme() != "http"
+                    && entry.url.scheme() != "https");
+            openable.then(|| entry.url.clone())
+        } else if let Some(content) = app.find_name::<SelectView<Option<Url>>>("gemini_content") {
+            content.selection().and_then(|url| (*url).clone())
+        } else {
+            unreachable!("view content and gemini_content missing");
+        }
+    }
 
-        let mut bookmarks = self.bookmarks.lock().unwrap();
+    pub fn add_bookmark_action(&mut self, url: Url, title: String, tags: String, folder: String) {
+        let tags = tags
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect();
+        let b = Bookmark {
+            title,
+            url,
+            tags,
+            folder: folder.trim().trim_matches('/').to_string(),
+        };
 
-        let index = bookmarks.insert(b.clone());
+        let mut bookmarks = self.bookmarks.lock().unwrap();
+        bookmarks.insert(b);
+        let entries = bookmarks.get_bookmarks();
+        drop(bookmarks);
 
-        // add to bookmark menu
+        // rebuild bookmark menu
         self.sender
             .send(Box::new(move |app| {
-                let url = b.url.clone();
-                let menu = app
-                    .menubar()
-                    .find_subtree("Bookmarks")
-                    .expect("bookmarks menu missing");
-                if let Some(i) = index {
-                    // replace element
-                    // add 3 to account for "Edit..." etc.
-                    menu.remove(i + 3);
-                    menu.insert_leaf(i + 3, &b.title, move |app| {
-                        app.user_data::<Controller>()
-                            .expect("controller missing")
-                            .open_url(url.clone(), true, 0);
-                    });
-                } else {
-                    // add new entry to end
-                    menu.add_leaf(&b.title, move |app| {
-                        app.user_data::<Controller>()
-                            .expect("controller missing")
-                            .open_url(url.clone(), true, 0);
-                    });
-                }
+                crate::ui::setup::setup_bookmark_menu(app, &entries);
             }))
             .unwrap();
     }
@@ -1765,25 +3177,73 @@ impl Controller {
             .lock()
             .unwrap();
         guard.remove(&b.url);
-        let bookmarks = guard.entries.clone();
+        let bookmarks = guard.get_bookmarks();
         drop(guard);
 
-        // redraw bookmark menu
-        let menutree = app
-            .menubar()
-            .find_subtree("Bookmarks")
-            .expect("bookmarks menu missing");
-        menutree.clear();
-        // re-add all bookmark entries
-        // respecting the order so add_bookmark_action works correctly
-        for entry in bookmarks.iter().rev() {
-            let url = entry.url.clone();
-            menutree.insert_leaf(3, &b.title, move |app| {
-                app.user_data::<Controller>()
-                    .expect("controller missing")
-                    .open_url(url.clone(), true, 0);
-            });
+        crate::ui::setup::setup_bookmark_menu(app, &bookmarks);
+    }
+
+    /// Swaps the positions of two bookmarks in the bookmark menu
+    pub fn swap_bookmarks_action(app: &mut Cursive, a: &Url, b: &Url) {
+        let mut guard = app
+            .user_data::<Controller>()
+            .expect("controller missing")
+            .bookmarks
+            .lock()
+            .unwrap();
+        guard.swap(a, b);
+        let bookmarks = guard.get_bookmarks();
+        drop(guard);
+
+        crate::ui::setup::setup_bookmark_menu(app, &bookmarks);
+    }
+
+    /// Exports the bookmarks. Relative paths are relative to the
+    /// download directory.
+    pub fn export_bookmarks_action(app: &mut Cursive, format: ExportFormat, path: &str) {
+        if path.is_empty() {
+            // do not pop the export dialog so user can make corrections
+            app.add_layer(Dialog::info("No filename given!"));
+            return;
         }
+        app.pop_layer();
+        let controller = app.user_data::<Controller>().expect("controller missing");
+        let content = controller.bookmarks.lock().unwrap().export(format);
+        let download_path = SETTINGS.read().unwrap().config.download_path.clone();
+        let path = Path::new(download_path.as_str()).join(path);
+        match std::fs::write(&path, content) {
+            Ok(()) => controller.set_message(&format!("Bookmarks exported to {}", path.display())),
+            Err(err) => controller.set_message(&format!(
+                "Could not export bookmarks to {}: {}",
+                path.display(),
+                err
+            )),
+        }
+    }
+
+    pub fn import_bookmarks_action(app: &mut Cursive, path: &str) {
+        if path.is_empty() {
+            // do not pop the import dialog so user can make corrections
+            app.add_layer(Dialog::info("No filename given!"));
+            return;
+        }
+        app.pop_layer();
+        let controller = app.user_data::<Controller>().expect("controller missing");
+        let mut bookmarks = controller.bookmarks.lock().unwrap();
+        let result = bookmarks.import_file(Path::new(path));
+        let entries = bookmarks.get_bookmarks();
+        drop(bookmarks);
+        match result {
+            Ok((added, merged)) => controller.set_message(&format!(
+                "Imported {} new bookmarks, merged {} known bookmarks",
+                added, merged
+            )),
+            Err(err) => controller.set_message(&format!(
+                "Could not import bookmarks from {}: {}",
+                path, err
+            )),
+        }
+        crate::ui::setup::setup_bookmark_menu(app, &entries);
     }
 
     pub fn remove_client_certificate_action(app: &mut Cursive, cc: &ClientCertificate) {
@@ -1834,29 +3294,31 @@ impl Controller {
     pub fn save_as_action(app: &mut Cursive, path: &str) {
         if !path.is_empty() {
             app.pop_layer();
+            app.user_data::<Controller>()
+                .expect("controller missing")
+                .save_page(path.to_string());
+        } else {
+            // do not pop the save dialog so user can make corrections
+            app.add_layer(Dialog::info("No filename given!"))
+        }
+    }
 
-            let path = path.to_string();
-            let controller = app.user_data::<Controller>().expect("controller missing");
-            controller.set_message(&format!("saving page as '{}'.", path));
+    pub fn save_page(&mut self, path: String) {
+        self.set_message(&format!("saving page as '{}'.", path));
 
-            let current_url = controller.current_url.lock().unwrap().clone();
+        let current_url = self.current_url.lock().unwrap().clone();
 
-            match current_url.scheme() {
-                "gopher" => {
-                    let item_type = ItemType::from_url(&current_url);
-                    match item_type {
-                        ItemType::Dir => controller.save_gophermap(path),
-                        ItemType::File => controller.save_textfile(path),
-                        _ => controller.set_message("cannot save this kind of page"),
-                    }
+        match current_url.scheme() {
+            "gopher" => {
+                let item_type = ItemType::from_url(&current_url);
+                match item_type {
+                    ItemType::Dir => self.save_gophermap(path),
+                    ItemType::File | ItemType::Html => self.save_textfile(path),
+                    _ => self.set_message("cannot save this kind of page"),
                 }
-                "about" | "gemini" => controller.save_gemini(path),
-                other => controller
-                    .set_message(&format!("failed to save page: unknown scheme {}", other)),
             }
-        } else {
-            // do not pop the save dialog so user can make corrections
-            app.add_layer(Dialog::info("No filename given!"))
+            "about" | "gemini" => self.save_gemini(path),
+            other => self.set_message(&format!("failed to save page: unknown scheme {}", other)),
         }
     }
 
@@ -1922,6 +3384,12 @@ impl Controller {
     }
 
     pub fn search(&mut self, search_str: String) {
+        self.highlight_search(search_str, true);
+    }
+
+    /// Highlights the matches of a search in the current page. If
+    /// `jump` is true, the next match is selected.
+    fn highlight_search(&mut self, search_str: String, jump: bool) {
         info!("Searching for {}", search_str);
         self.current_search = search_str.clone();
         let sender = self.sender.clone();
@@ -1931,61 +3399,34 @@ impl Controller {
                 if let Some(mut content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
                     for (index, listitem) in content.try_iter_mut().enumerate() {
                         let (label, _item) = listitem; //(&mut SpannedString<Style>, &mut GopherMapEntry)
-                        let label_source = label.source();
-                        if !search_str.is_empty() && label_source.contains(&search_str) {
+                        if !search_str.is_empty() && label.source().contains(&search_str) {
                             hits.push(index);
-                            let split = label_source.split(&search_str);
-                            let mut l = StyledString::new();
-
-                            let vec: Vec<&str> = split.collect();
-                            for (pos, part) in vec.iter().enumerate() {
-                                l.append(*part);
-                                if pos != vec.len() - 1 {
-                                    //l.append_styled(&search_str, ColorStyle::new(Color::Dark(BaseColor::Red), ColorType::Palette(PaletteColor::Highlight)));
-                                    l.append_styled(&search_str, ColorStyle::highlight());
-                                }
-                            }
-                            *label = l.clone();
-                        } else {
-                            // This will remove previous search results
-                            let mut l = StyledString::new();
-                            l.append(label_source);
-                            *label = l.clone();
                         }
+                        // This also removes previous search results
+                        *label = Controller::highlight_matches(label, &search_str);
+                    }
+                    if jump {
+                        let scroll_view = app.find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                            "content_scroll",
+                            ).expect("gopher scroll view missing");
+                        move_to_next_item(content, scroll_view, Direction::Next, hits.clone());
                     }
-                    let scroll_view = app.find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
-                        "content_scroll",
-                        ).expect("gopher scroll view missing");
-                    move_to_next_item(content, scroll_view, Direction::Next, hits.clone());
                 } else if let Some(mut content) = app.find_name::<SelectView<Option<Url>>>("gemini_content") {
                     info!("Found gemini content!!!!");
                     for (index, listitem) in content.try_iter_mut().enumerate() {
                         let (label, _item) = listitem; //(&mut SpannedString<Style>, &mut GopherMapEntry)
-                        let label_source = label.source();
-                        if !search_str.is_empty() && label_source.contains(&search_str) {
+                        if !search_str.is_empty() && label.source().contains(&search_str) {
                             hits.push(index);
-                            let split = label_source.split(&search_str);
-                            let mut l = StyledString::new();
-
-                            let vec: Vec<&str> = split.collect();
-                            for (pos, part) in vec.iter().enumerate() {
-                                l.append(*part);
-                                if pos != vec.len() - 1 {
-                                    l.append_styled(&search_str, ColorStyle::highlight());
-                                }
-                            }
-                            *label = l.clone();
-                        } else {
-                            // This will remove previous search results
-                            let mut l = StyledString::new();
-                            l.append(label_source);
-                            *label = l.clone();
                         }
+                        // This also removes previous search results
+                        *label = Controller::highlight_matches(label, &search_str);
+                    }
+                    if jump {
+                        let scroll_view = app.find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                            "gemini_content_scroll",
+                            ).expect("gemini scroll view missing");
+                        move_to_next_item(content, scroll_view, Direction::Next, hits.clone());
                     }
-                    let scroll_view = app.find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
-                        "gemini_content_scroll",
-                        ).expect("gemini scroll view missing");
-                    move_to_next_item(content, scroll_view, Direction::Next, hits.clone());
                 } else {
                     unreachable!("view content and gemini_content missing");
                 }
@@ -1996,6 +3437,50 @@ impl Controller {
             })).unwrap();
     }
 
+    /// Highlights all occurrences of `search_str` in a label and
+    /// removes previous highlights, keeping the styles of the label
+    fn highlight_matches(label: &StyledString, search_str: &str) -> StyledString {
+        let highlight = Style::from(ColorStyle::highlight());
+        let source = label.source();
+        let matches = if search_str.is_empty() {
+            Vec::new()
+        } else {
+            source
+                .match_indices(search_str)
+                .map(|(start, m)| (start, start + m.len()))
+                .collect::<Vec<_>>()
+        };
+        let mut result = StyledString::new();
+        let mut offset = 0;
+        for span in label.spans() {
+            let style = if *span.attr == highlight {
+                Style::none()
+            } else {
+                *span.attr
+            };
+            let end = offset + span.content.len();
+            let mut pos = offset;
+            while pos < end {
+                // split the span at the boundaries of the matches
+                let (next, style) = match matches.iter().find(|(s, e)| *s <= pos && pos < *e) {
+                    Some((_, e)) => (std::cmp::min(*e, end), highlight),
+                    None => {
+                        let next = matches
+                            .iter()
+                            .map(|(s, _)| *s)
+                            .find(|s| *s > pos)
+                            .unwrap_or(end);
+                        (std::cmp::min(next, end), style)
+                    }
+                };
+                result.append_styled(&source[pos..next], style);
+                pos = next;
+            }
+            offset = end;
+        }
+        result
+    }
+
     pub fn set_search_hits(&mut self, hits: Vec<usize>) {
         self.current_search_results = hits;
     }
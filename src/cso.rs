@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use url::Url;
+
+// CSO/ph phone-book protocol, see https://tools.ietf.org/html/rfc2378
+
+/// Default TCP port of a CSO nameserver
+const CSO_PORT: u16 = 105;
+
+/// A single line of a CSO server response, e.g.
+/// `-200:1:        name: Doe, John`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponseLine {
+    /// The numeric result code. Negative codes indicate that more
+    /// lines belonging to the same response will follow.
+    pub code: i32,
+    /// Number of the record (or field description) the line belongs to
+    pub index: Option<u32>,
+    /// Name of the field. Empty for continuation lines.
+    pub field: String,
+    /// The value of the field or the message of the server
+    pub value: String,
+}
+
+impl ResponseLine {
+    /// Parses a raw line into a ResponseLine
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let mut parts = line.splitn(2, ':');
+        let code = parts.next()?.trim().parse::<i32>().ok()?;
+        let rest = parts.next().unwrap_or_default();
+
+        // Data lines have the form code:index:field:value, all other
+        // lines are code:message.
+        let mut data = rest.splitn(3, ':');
+        if let (Some(index), Some(field), Some(value)) = (data.next(), data.next(), data.next()) {
+            if let Ok(index) = index.trim().parse::<u32>() {
+                return Some(ResponseLine {
+                    code,
+                    index: Some(index),
+                    field: field.trim().to_string(),
+                    value: value.trim_start_matches(' ').to_string(),
+                });
+            }
+        }
+        Some(ResponseLine {
+            code,
+            index: None,
+            field: String::new(),
+            value: rest.to_string(),
+        })
+    }
+
+    /// Returns true if this is the last line of a response
+    fn is_final(&self) -> bool {
+        self.code >= 200
+    }
+
+    fn is_error(&self) -> bool {
+        self.code.abs() >= 300
+    }
+}
+
+/// Sends a single command to the CSO server given in the URL and
+/// returns all lines of the response.
+pub fn request(url: &Url, command: &str) -> Result<Vec<ResponseLine>, String> {
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port().unwrap_or(CSO_PORT);
+    let server_details = format!("{}:{}", host, port);
+    info!("cso::request(): {} to {}", command, server_details);
+
+    let mut stream = TcpStream::connect(&server_details)
+        .map_err(|err| format!("Couldn't connect to server: {}", err))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30))).ok();
+    write!(stream, "{}\r\n", command).map_err(|err| format!("I/O error: {}", err))?;
+
+    let mut lines = Vec::new();
+    let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+    loop {
+        let mut buf = String::new();
+        match reader.read_line(&mut buf) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(err) => return Err(format!("I/O error: {}", err)),
+        }
+        match ResponseLine::parse(&buf) {
+            Some(line) => {
+                let last = line.is_final();
+                lines.push(line);
+                if last {
+                    break;
+                }
+            }
+            None => warn!("Invalid CSO response line: {}", buf.trim_end()),
+        }
+    }
+    // Be polite and end the session
+    write!(stream, "quit\r\n").ok();
+    Ok(lines)
+}
+
+/// Formats the response of a `query` command as a table of records.
+pub fn format_records(lines: &[ResponseLine]) -> String {
+    let mut records: BTreeMap<u32, Vec<(String, String)>> = BTreeMap::new();
+    let mut messages = Vec::new();
+    for line in lines {
+        match line.index {
+            Some(index) if !line.is_error() => {
+                let record = records.entry(index).or_default();
+                if line.field.is_empty() {
+                    // Continuation of a multi-line field value
+                    if let Some((_, value)) = record.last_mut() {
+                        value.push('\n');
+                        value.push_str(&line.value);
+                        continue;
+                    }
+                }
+                record.push((line.field.clone(), line.value.clone()));
+            }
+            _ => messages.push(line.value.clone()),
+        }
+    }
+
+    if records.is_empty() {
+        return messages.join("\n");
+    }
+
+    let width = records
+        .values()
+        .flatten()
+        .map(|(field, _)| field.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut result = String::new();
+    for (index, record) in records {
+        result.push_str(&format!("Record {}\n", index));
+        for (field, value) in record {
+            let mut values = value.lines();
+            result.push_str(&format!(
+                "  {:>width$}: {}\n",
+                field,
+                values.next().unwrap_or_default(),
+                width = width
+            ));
+            for value in values {
+                result.push_str(&format!("  {:>width$}  {}\n", "", value, width = width));
+            }
+        }
+        result.push('\n');
+    }
+    result.push_str(&messages.join("\n"));
+    result
+}
+
+/// Formats the response of a `fields` command. Each field is
+/// described by two lines: one with its properties and one with
+/// a human readable description.
+pub fn format_fields(lines: &[ResponseLine]) -> String {
+    let mut fields: BTreeMap<u32, (String, Vec<String>)> = BTreeMap::new();
+    let mut messages = Vec::new();
+    for line in lines {
+        match line.index {
+            Some(index) if !line.is_error() => {
+                let (name, values) = fields.entry(index).or_default();
+                *name = line.field.clone();
+                values.push(line.value.clone());
+            }
+            _ => messages.push(line.value.clone()),
+        }
+    }
+
+    let mut result = String::new();
+    for (name, values) in fields.values() {
+        // The second line is the description, the first the properties
+        let (properties, description) = match values.as_slice() {
+            [properties, description, ..] => (properties.as_str(), description.as_str()),
+            [properties] => (properties.as_str(), ""),
+            [] => ("", ""),
+        };
+        result.push_str(&format!("{:<12} {}\n", name, description));
+        if !properties.is_empty() {
+            result.push_str(&format!("{:<12} ({})\n", "", properties));
+        }
+    }
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result.push_str(&messages.join("\n"));
+    result
+}
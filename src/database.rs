@@ -0,0 +1,13 @@
+use rusqlite::{Connection, Error, Result};
+use std::path::PathBuf;
+
+// The SQLite databases of the history, the feeds and the reading list
+// are kept in the configuration directory.
+
+/// Opens a database in the configuration directory
+pub fn open(filename: &str) -> Result<Connection> {
+    let mut path = dirs::config_dir().ok_or_else(|| Error::InvalidPath(PathBuf::from(filename)))?;
+    path.push(env!("CARGO_PKG_NAME"));
+    path.push(filename);
+    Connection::open(path)
+}
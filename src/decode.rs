@@ -0,0 +1,174 @@
+// Decoders for the encoded gopher item types 4 (BinHex) and 6 (uuencoded)
+
+/// A file extracted from an encoded download
+#[derive(Clone, Debug)]
+pub struct DecodedFile {
+    /// Original file name stored in the encoded file
+    pub name: String,
+    /// UNIX file mode, if known
+    pub mode: Option<u32>,
+    /// The decoded content (for BinHex only the data fork)
+    pub data: Vec<u8>,
+}
+
+/// Decodes a UNIX uuencoded file.
+///
+/// Everything before the `begin <mode> <filename>` line (e.g. mail
+/// headers) is ignored.
+pub fn uudecode(input: &[u8]) -> Result<DecodedFile, &'static str> {
+    let text = String::from_utf8_lossy(input);
+    let mut lines = text.lines();
+    let (mode, name) = loop {
+        let line = lines.next().ok_or("no begin line found")?;
+        if let Some(rest) = line.strip_prefix("begin ") {
+            let mut parts = rest.trim().splitn(2, ' ');
+            let mode = u32::from_str_radix(parts.next().unwrap_or_default(), 8)
+                .map_err(|_| "invalid file mode")?;
+            let name = parts.next().ok_or("missing file name")?.trim().to_string();
+            break (mode, name);
+        }
+    };
+
+    let mut data = Vec::new();
+    for line in lines {
+        if line.trim_end() == "end" {
+            return Ok(DecodedFile {
+                name,
+                mode: Some(mode),
+                data,
+            });
+        }
+        let bytes = line.as_bytes();
+        if bytes.is_empty() {
+            continue;
+        }
+        // The first character encodes the number of bytes in this line.
+        // A length of zero ("`" or " ") marks the end of the data.
+        let len = (bytes[0].wrapping_sub(b' ') & 0x3f) as usize;
+        if len == 0 {
+            continue;
+        }
+        let sextets = bytes[1..]
+            .iter()
+            .map(|c| c.wrapping_sub(b' ') & 0x3f)
+            .collect::<Vec<u8>>();
+        let mut decoded = Vec::with_capacity(len + 2);
+        for chunk in sextets.chunks(4) {
+            let c = |i: usize| chunk.get(i).copied().unwrap_or(0);
+            decoded.push((c(0) << 2) | (c(1) >> 4));
+            decoded.push((c(1) << 4) | (c(2) >> 2));
+            decoded.push((c(2) << 6) | c(3));
+        }
+        if decoded.len() < len {
+            return Err("truncated line in uuencoded data");
+        }
+        data.extend_from_slice(&decoded[..len]);
+    }
+    Err("no end line found")
+}
+
+/// The 64 characters used by BinHex 4.0, in order of their value
+const BINHEX_ALPHABET: &[u8] = b"!\"#$%&'()*+,-012345689@ABCDEFGHIJKLMNPQRSTUVXYZ[`abcdefhijklmpqr";
+
+/// Marker for run length encoded sequences in BinHex data
+const BINHEX_RUN_MARKER: u8 = 0x90;
+
+/// Decodes a BinHex 4.0 file and returns its data fork. The resource
+/// fork has no equivalent on most systems and is dropped.
+pub fn binhex(input: &[u8]) -> Result<DecodedFile, &'static str> {
+    let text = String::from_utf8_lossy(input);
+    // The encoded data starts at the first colon after the
+    // "(This file must be converted with BinHex 4.0)" line
+    let start = match text.find("(This file must be converted with BinHex") {
+        Some(pos) => pos + text[pos..].find('\n').ok_or("no BinHex data found")?,
+        None => 0,
+    };
+    let data = &text[start..];
+    let data = &data[data.find(':').ok_or("no BinHex data found")? + 1..];
+    let data = &data[..data.find(':').ok_or("BinHex data is not terminated")?];
+
+    // 6 bit decoding
+    let mut packed = Vec::with_capacity(data.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut nbits = 0;
+    for c in data.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = BINHEX_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or("invalid character in BinHex data")?;
+        bits = (bits << 6) | value as u32;
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            packed.push((bits >> nbits) as u8);
+        }
+    }
+
+    // Run length decoding
+    let mut bytes = Vec::with_capacity(packed.len());
+    let mut i = 0;
+    while i < packed.len() {
+        if packed[i] == BINHEX_RUN_MARKER {
+            let count = *packed.get(i + 1).ok_or("truncated BinHex data")?;
+            if count == 0 {
+                // escaped marker byte
+                bytes.push(BINHEX_RUN_MARKER);
+            } else {
+                let last = *bytes.last().ok_or("invalid run length in BinHex data")?;
+                bytes.extend(std::iter::repeat_n(last, count as usize - 1));
+            }
+            i += 2;
+        } else {
+            bytes.push(packed[i]);
+            i += 1;
+        }
+    }
+
+    // Header: name length, name, version, type, creator, flags,
+    // data fork length, resource fork length, header CRC
+    let name_len = *bytes.first().ok_or("empty BinHex data")? as usize;
+    let header_len = 1 + name_len + 1 + 4 + 4 + 2 + 4 + 4;
+    if bytes.len() < header_len + 2 {
+        return Err("truncated BinHex header");
+    }
+    let name = String::from_utf8_lossy(&bytes[1..1 + name_len]).into_owned();
+    let read_u32 = |pos: usize| {
+        u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+    };
+    let read_u16 = |pos: usize| u16::from_be_bytes([bytes[pos], bytes[pos + 1]]);
+    let data_len = read_u32(header_len - 8) as usize;
+    if crc16(&bytes[..header_len]) != read_u16(header_len) {
+        return Err("BinHex header checksum mismatch");
+    }
+
+    let data_start = header_len + 2;
+    if bytes.len() < data_start + data_len + 2 {
+        return Err("truncated BinHex data fork");
+    }
+    let data = bytes[data_start..data_start + data_len].to_vec();
+    if crc16(&data) != read_u16(data_start + data_len) {
+        return Err("BinHex data fork checksum mismatch");
+    }
+
+    Ok(DecodedFile {
+        name,
+        mode: None,
+        data,
+    })
+}
+
+/// CRC-CCITT (XMODEM) as used by BinHex 4.0
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
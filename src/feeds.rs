@@ -0,0 +1,482 @@
+use gemtext::Node;
+use roxmltree::Document;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, ToSql};
+use std::convert::TryFrom;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::{format_description, Date, Month, OffsetDateTime};
+use url::Url;
+
+// Subscriptions to gemini pages, Atom or RSS feeds and gophermaps.
+// Gemini pages follow the companion specification for gemlogs: every
+// link line whose label starts with a date in the form YYYY-MM-DD is
+// an entry of the feed.
+// https://geminiprotocol.net/docs/companion/subscription.gmi
+// Watched gophermaps are subscriptions of their own kind, every link
+// is an entry (see phlogs.rs).
+
+/// An entry of a feed
+#[derive(Clone, Debug)]
+pub struct FeedEntry {
+    pub url: Url,
+    pub title: String,
+    pub date: Option<Date>,
+}
+
+/// Title and entries of a fetched feed
+#[derive(Clone, Debug, Default)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    /// Converts the feed into gemtext nodes listing its entries as
+    /// dated links
+    pub fn nodes(&self) -> Vec<Node> {
+        let mut nodes = vec![Node::Heading {
+            level: 1,
+            body: self.title.clone().unwrap_or_else(|| "Feed".to_string()),
+        }];
+        if let Some(description) = &self.description {
+            nodes.push(Node::Text(description.clone()));
+        }
+        nodes.push(Node::Text(String::new()));
+        for entry in &self.entries {
+            let name = match entry.date {
+                Some(date) => format!("{} {}", date, entry.title),
+                None => entry.title.clone(),
+            };
+            nodes.push(Node::Link {
+                to: entry.url.to_string(),
+                name: Some(name),
+            });
+        }
+        if self.entries.is_empty() {
+            nodes.push(Node::Text("This feed has no entries.".to_string()));
+        }
+        nodes
+    }
+}
+
+/// What a subscription follows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    /// A gemini page or an Atom or RSS feed
+    Feed,
+    /// A gophermap whose new items are listed
+    Phlog,
+}
+
+impl SourceKind {
+    /// The about page listing the entries of this kind
+    pub fn page_url(self) -> &'static str {
+        match self {
+            SourceKind::Feed => "about:feeds",
+            SourceKind::Phlog => "about:phlogs",
+        }
+    }
+}
+
+impl ToSql for SourceKind {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            SourceKind::Feed => "feed",
+            SourceKind::Phlog => "phlog",
+        }
+        .into())
+    }
+}
+
+impl FromSql for SourceKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "feed" => Ok(SourceKind::Feed),
+            "phlog" => Ok(SourceKind::Phlog),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Subscription {
+    pub id: i64,
+    pub kind: SourceKind,
+    pub url: Url,
+    pub title: String,
+}
+
+impl Subscription {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Subscription {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            url: row.get(2)?,
+            title: row.get(3)?,
+        })
+    }
+}
+
+/// A stored feed entry with the title of its subscription
+#[derive(Clone, Debug)]
+pub struct StoredEntry {
+    pub url: Url,
+    pub title: String,
+    pub date: Date,
+    pub feed: String,
+    pub read: bool,
+}
+
+/// Subscriptions of both kinds and their entries
+#[derive(Debug)]
+pub struct Feeds {
+    sql: Connection,
+}
+
+impl Feeds {
+    pub fn new() -> Result<Self> {
+        info!("Creating feeds object");
+        let connection = crate::database::open("feeds.db")?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS subscriptions (
+             id INTEGER PRIMARY KEY,
+             kind TEXT NOT NULL DEFAULT 'feed',
+             url TEXT NOT NULL UNIQUE,
+             title TEXT NOT NULL,
+             lastchecked DATETIME
+         )",
+            [],
+        )?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS entries (
+             id INTEGER PRIMARY KEY,
+             subscription INTEGER NOT NULL,
+             url TEXT NOT NULL,
+             title TEXT NOT NULL,
+             published DATE,
+             firstseen DATETIME DEFAULT CURRENT_TIMESTAMP,
+             read NUMBER NOT NULL DEFAULT 0,
+             UNIQUE (subscription, url)
+         )",
+            [],
+        )?;
+        Ok(Feeds { sql: connection })
+    }
+
+    /// Adds a subscription and returns it. Subscribing to a URL twice
+    /// returns the existing subscription.
+    pub fn subscribe(&mut self, kind: SourceKind, url: &Url, title: &str) -> Result<Subscription> {
+        self.sql.execute(
+            "INSERT OR IGNORE INTO subscriptions (kind, url, title) VALUES (?1, ?2, ?3)",
+            params![kind, url.as_str(), title],
+        )?;
+        self.sql.query_row(
+            "SELECT id, kind, url, title FROM subscriptions WHERE url=?1",
+            params![url.as_str()],
+            Subscription::from_row,
+        )
+    }
+
+    /// Removes a subscription and all of its entries
+    pub fn unsubscribe(&mut self, id: i64) -> Result<()> {
+        self.sql
+            .execute("DELETE FROM entries WHERE subscription=?1", params![id])?;
+        self.sql
+            .execute("DELETE FROM subscriptions WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    /// Returns the subscription to the URL if there is one
+    pub fn get_subscription(&self, url: &Url) -> Option<Subscription> {
+        self.sql
+            .query_row(
+                "SELECT id, kind, url, title FROM subscriptions WHERE url=?1",
+                params![url.as_str()],
+                Subscription::from_row,
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn subscriptions(&self) -> Result<Vec<Subscription>> {
+        let mut stmt = self
+            .sql
+            .prepare("SELECT id, kind, url, title FROM subscriptions ORDER BY title")?;
+        let rows = stmt.query_map([], Subscription::from_row)?;
+        rows.collect()
+    }
+
+    /// Stores the entries of a fetched feed and returns the number of
+    /// entries that were not seen before. New entries are stored as
+    /// unread unless `read` is set.
+    pub fn update(
+        &mut self,
+        subscription: &Subscription,
+        feed: &Feed,
+        read: bool,
+    ) -> Result<usize> {
+        let mut new_entries = 0;
+        for entry in &feed.entries {
+            new_entries += self.sql.execute(
+                "INSERT OR IGNORE INTO entries (subscription, url, title, published, read)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    subscription.id,
+                    entry.url.as_str(),
+                    entry.title,
+                    entry.date,
+                    read
+                ],
+            )?;
+        }
+        self.sql.execute(
+            "UPDATE subscriptions SET lastchecked=datetime('NOW') WHERE id=?1",
+            params![subscription.id],
+        )?;
+        if let Some(title) = &feed.title {
+            self.sql.execute(
+                "UPDATE subscriptions SET title=?1 WHERE id=?2",
+                params![title, subscription.id],
+            )?;
+        }
+        Ok(new_entries)
+    }
+
+    /// Marks all entries linking to the URL as read
+    pub fn mark_read(&mut self, url: &Url) -> Result<()> {
+        self.sql.execute(
+            "UPDATE entries SET read=1 WHERE url=?1",
+            params![url.as_str()],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_all_read(&mut self, kind: SourceKind) -> Result<()> {
+        self.sql.execute(
+            "UPDATE entries SET read=1 WHERE subscription IN
+             (SELECT id FROM subscriptions WHERE kind=?1)",
+            params![kind],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the newest entries of all subscriptions of a kind.
+    /// Entries without a date are dated by the day they were first
+    /// seen.
+    pub fn latest_entries(&self, kind: SourceKind, num_items: usize) -> Result<Vec<StoredEntry>> {
+        let mut stmt = self.sql.prepare(
+            "SELECT entries.url, entries.title,
+                    COALESCE(entries.published, date(entries.firstseen)) AS day,
+                    subscriptions.title, entries.read
+             FROM entries JOIN subscriptions ON entries.subscription = subscriptions.id
+             WHERE subscriptions.kind = ?1
+             ORDER BY day DESC, entries.id DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![kind, num_items as u32], |row| {
+            Ok(StoredEntry {
+                url: row.get(0)?,
+                title: row.get(1)?,
+                date: row.get(2)?,
+                feed: row.get(3)?,
+                read: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+/// Parses a date in the form YYYY-MM-DD at the start of the text
+fn parse_date(text: &str) -> Option<Date> {
+    let format = format_description::parse("[year]-[month]-[day]").ok()?;
+    Date::parse(text.get(..10)?, &format).ok()
+}
+
+/// Finds the title and the dated link lines of a gemini page
+pub fn parse_gemini(text: &str, base_url: &Url) -> Feed {
+    let mut feed = Feed::default();
+    for line in text.lines() {
+        if let Some(heading) = line.strip_prefix("# ") {
+            if feed.title.is_none() {
+                feed.title = Some(heading.trim().to_string());
+            }
+        } else if let Some(link) = line.strip_prefix("=>") {
+            let link = link.trim_start();
+            let (target, label) = link.split_once(char::is_whitespace).unwrap_or((link, ""));
+            let label = label.trim();
+            let (url, date) = match (base_url.join(target), parse_date(label)) {
+                (Ok(url), Some(date)) => (url, date),
+                _ => continue,
+            };
+            // Titles are often separated from the date by a dash
+            let title = label[10..].trim_start_matches([' ', '\t', '-', ':', '\u{2013}']);
+            feed.entries.push(FeedEntry {
+                url,
+                title: title.to_string(),
+                date: Some(date),
+            });
+        }
+    }
+    feed
+}
+
+/// Returns true for the MIME types of Atom and RSS feeds
+pub fn is_xml_feed(mime: &mime::Mime) -> bool {
+    matches!(
+        mime.essence_str(),
+        "application/atom+xml" | "application/rss+xml"
+    )
+}
+
+/// Returns true if a gopher file is an Atom or RSS feed judging by its
+/// name. Generic XML files have to be parsed to find out.
+pub fn has_feed_extension(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".atom") || path.ends_with(".rss")
+}
+
+/// Parses a date of an RSS feed like "Mon, 31 Jan 2022 10:00:00 GMT".
+/// Many feeds don't follow RFC 2822 exactly, so only the day, month
+/// and year are read if it fails.
+fn parse_rfc2822_date(text: &str) -> Option<Date> {
+    if let Ok(datetime) = OffsetDateTime::parse(text, &Rfc2822) {
+        return Some(datetime.date());
+    }
+    let mut parts = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .skip_while(|part| part.parse::<u8>().is_err());
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_lowercase();
+    let month = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|name| month.starts_with(name))?;
+    let year = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, Month::try_from(month as u8 + 1).ok()?, day).ok()
+}
+
+/// Parses the date of an Atom entry or a Dublin Core date
+fn parse_rfc3339_date(text: &str) -> Option<Date> {
+    OffsetDateTime::parse(text, &Rfc3339)
+        .ok()
+        .map(|datetime| datetime.date())
+        .or_else(|| parse_date(text))
+}
+
+/// Parses an Atom or RSS (0.9x, 1.0 and 2.0) document. Returns None if
+/// the text is not a feed.
+pub fn parse_xml(text: &str, base_url: &Url) -> Option<Feed> {
+    let document = Document::parse(text).ok()?;
+    let root = document.root_element();
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+    let mut feed = Feed::default();
+    match root.tag_name().name() {
+        "feed" => {
+            feed.title = child_text(root, "title");
+            feed.description = child_text(root, "subtitle");
+            for entry in root.children().filter(|c| c.tag_name().name() == "entry") {
+                // The alternate link points to the entry itself
+                let link = entry
+                    .children()
+                    .filter(|c| c.tag_name().name() == "link")
+                    .find(|c| matches!(c.attribute("rel"), None | Some("alternate")))
+                    .and_then(|c| c.attribute("href"))
+                    .map(str::to_string)
+                    .or_else(|| child_text(entry, "id"));
+                let Some(url) = link.and_then(|link| base_url.join(&link).ok()) else {
+                    continue;
+                };
+                let date = child_text(entry, "published")
+                    .or_else(|| child_text(entry, "updated"))
+                    .and_then(|date| parse_rfc3339_date(&date));
+                feed.entries.push(FeedEntry {
+                    title: child_text(entry, "title").unwrap_or_else(|| url.to_string()),
+                    url,
+                    date,
+                });
+            }
+        }
+        "rss" | "RDF" => {
+            let channel = root.children().find(|c| c.tag_name().name() == "channel")?;
+            feed.title = child_text(channel, "title");
+            feed.description = child_text(channel, "description");
+            // Items are children of the channel in RSS 2.0 and siblings
+            // of it in RSS 1.0
+            for item in root.descendants().filter(|c| c.tag_name().name() == "item") {
+                let link = child_text(item, "link").or_else(|| child_text(item, "guid"));
+                let Some(url) = link.and_then(|link| base_url.join(&link).ok()) else {
+                    continue;
+                };
+                let date = match child_text(item, "pubDate") {
+                    Some(date) => parse_rfc2822_date(&date),
+                    None => child_text(item, "date").and_then(|date| parse_rfc3339_date(&date)),
+                };
+                feed.entries.push(FeedEntry {
+                    title: child_text(item, "title").unwrap_or_else(|| url.to_string()),
+                    url,
+                    date,
+                });
+            }
+        }
+        _ => return None,
+    }
+    Some(feed)
+}
+
+/// Renders the about page of a kind listing the newest entries
+/// grouped by day
+pub fn page(kind: SourceKind, entries: &[StoredEntry], subscriptions: usize) -> String {
+    let (title, entry_name, subscription_name) = match kind {
+        SourceKind::Feed => ("Feeds", "entries", "subscriptions"),
+        SourceKind::Phlog => ("New in your phlogs", "items", "watched gophermaps"),
+    };
+    let mut page = format!("# {}\n\n", title);
+    if subscriptions == 0 {
+        page.push_str(match kind {
+            SourceKind::Feed => {
+                "You have not subscribed to any feeds yet. Use \"Subscribe to this page\" \
+                 in the Feeds menu on a gemlog or an Atom or RSS feed to follow it.\n"
+            }
+            SourceKind::Phlog => {
+                "You are not watching any phlogs yet. Use \"Watch this gophermap\" \
+                 in the Feeds menu on a phlog to get notified about new posts.\n"
+            }
+        });
+        return page;
+    }
+    let unread = entries.iter().filter(|e| !e.read).count();
+    page.push_str(&format!(
+        "{} unread {} from {} {}.\n\n",
+        unread, entry_name, subscriptions, subscription_name
+    ));
+    page.push_str(&format!(
+        "=> {}?mark-read Mark all {} as read\n",
+        kind.page_url(),
+        entry_name
+    ));
+    let mut day = None;
+    for entry in entries {
+        if day != Some(entry.date) {
+            day = Some(entry.date);
+            page.push_str(&format!("\n## {}\n\n", entry.date));
+        }
+        page.push_str(&format!(
+            "=> {} {}{}: {}\n",
+            entry.url,
+            if entry.read { "" } else { "[new] " },
+            entry.feed,
+            entry.title
+        ));
+    }
+    page
+}
@@ -1,5 +1,10 @@
 extern crate gemtext;
+use crate::highlight::Token;
+use crate::SETTINGS;
 use cursive::utils::lines::simple::{make_lines, LinesIterator};
+use cursive::utils::markup::StyledString;
+use std::ops::Range;
+use unicode_width::UnicodeWidthChar;
 use url::Url;
 // https://gemini.circumlunar.space/docs/spec-spec.txt
 
@@ -7,13 +12,225 @@ use url::Url;
 pub enum GeminiType {
     Text,
     Gemini,
+    Html,
+    Markdown,
+    /// Atom or RSS feed
+    Feed,
 }
 
-pub fn parse(text: &str, base_url: &Url, viewport_width: usize) -> Vec<(String, Option<Url>)> {
-    let mut nodes = gemtext::parse(text);
+impl GeminiType {
+    /// Name of the type used when pages are stored
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeminiType::Text => "text",
+            GeminiType::Gemini => "gemini",
+            GeminiType::Html => "html",
+            GeminiType::Markdown => "markdown",
+            GeminiType::Feed => "feed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GeminiType> {
+        match name {
+            "text" => Some(GeminiType::Text),
+            "gemini" => Some(GeminiType::Gemini),
+            "html" => Some(GeminiType::Html),
+            "markdown" => Some(GeminiType::Markdown),
+            "feed" => Some(GeminiType::Feed),
+            _ => None,
+        }
+    }
+}
+
+/// Semantic roles of the parts of a rendered gemini page. Each role is
+/// styled by the entry of the same name in the `[gemini]` table of the
+/// active theme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Text,
+    Heading1,
+    Heading2,
+    Heading3,
+    GeminiLink,
+    GopherLink,
+    WebLink,
+    OtherLink,
+    VisitedLink,
+    BrokenLink,
+    Quote,
+    Preformatted,
+    PreformattedCaption,
+    ListBullet,
+    CodeKeyword,
+    CodeString,
+    CodeComment,
+    CodeNumber,
+}
+
+impl Role {
+    /// Name of the role in theme files
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Text => "text",
+            Role::Heading1 => "heading1",
+            Role::Heading2 => "heading2",
+            Role::Heading3 => "heading3",
+            Role::GeminiLink => "link_gemini",
+            Role::GopherLink => "link_gopher",
+            Role::WebLink => "link_web",
+            Role::OtherLink => "link_other",
+            Role::VisitedLink => "link_visited",
+            Role::BrokenLink => "link_broken",
+            Role::Quote => "quote",
+            Role::Preformatted => "preformatted",
+            Role::PreformattedCaption => "preformatted_caption",
+            Role::ListBullet => "list_bullet",
+            Role::CodeKeyword => "code_keyword",
+            Role::CodeString => "code_string",
+            Role::CodeComment => "code_comment",
+            Role::CodeNumber => "code_number",
+        }
+    }
+
+    fn heading(level: u8) -> Role {
+        match level {
+            1 => Role::Heading1,
+            2 => Role::Heading2,
+            _ => Role::Heading3,
+        }
+    }
+
+    fn code(token: Token) -> Role {
+        match token {
+            Token::Keyword => Role::CodeKeyword,
+            Token::String => Role::CodeString,
+            Token::Comment => Role::CodeComment,
+            Token::Number => Role::CodeNumber,
+        }
+    }
+
+    fn link(url: &Url) -> Role {
+        match url.scheme() {
+            "gemini" => Role::GeminiLink,
+            "gopher" => Role::GopherLink,
+            "https" | "http" => Role::WebLink,
+            _ => Role::OtherLink,
+        }
+    }
+}
+
+/// Display state of a preformatted block
+#[derive(Clone, Debug, Default)]
+pub struct PreformattedBlock {
+    /// Alt text, shown as caption
+    pub alt: String,
+    /// Folded blocks only show their caption
+    pub folded: bool,
+    /// Number of columns the block is scrolled to the right
+    pub offset: usize,
+    /// Rows of the block in the rendered page, including the caption
+    pub rows: Range<usize>,
+}
+
+pub fn parse(
+    text: &str,
+    base_url: &Url,
+    viewport_width: usize,
+    visited: &dyn Fn(&Url) -> bool,
+    blocks: &mut Vec<PreformattedBlock>,
+) -> Vec<(StyledString, Option<Url>)> {
+    let nodes = gemtext::parse(text);
+    render(
+        nodes,
+        &alt_texts(text),
+        base_url,
+        viewport_width,
+        visited,
+        blocks,
+    )
+}
+
+/// Returns the text of the first heading outside of preformatted
+/// blocks, which is used as title of the page
+pub fn title(text: &str) -> Option<String> {
+    let mut preformatted = false;
+    for line in text.lines() {
+        if line.starts_with("```") {
+            preformatted = !preformatted;
+        } else if let Some(heading) = line.strip_prefix('#').filter(|_| !preformatted) {
+            let heading = heading.trim_start_matches('#').trim();
+            if !heading.is_empty() {
+                return Some(heading.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Returns the alt texts of all preformatted blocks. The gemtext
+/// crate drops them.
+fn alt_texts(text: &str) -> Vec<String> {
+    let mut alt_texts = Vec::new();
+    let mut preformatted = false;
+    for line in text.lines() {
+        if let Some(alt) = line.strip_prefix("```") {
+            if !preformatted {
+                alt_texts.push(alt.trim().to_string());
+            }
+            preformatted = !preformatted;
+        }
+    }
+    alt_texts
+}
+
+/// Removes the first `columns` columns of a styled line
+fn skip_columns(line: &StyledString, columns: usize) -> StyledString {
+    let mut result = StyledString::new();
+    let mut skipped = 0;
+    for span in line.spans() {
+        let mut content = span.content;
+        while skipped < columns {
+            match content.chars().next() {
+                Some(c) => {
+                    skipped += c.width().unwrap_or(0);
+                    content = &content[c.len_utf8()..];
+                }
+                None => break,
+            }
+        }
+        if !content.is_empty() {
+            result.append_styled(content, *span.attr);
+        }
+    }
+    result
+}
+
+/// Renders gemtext nodes into lines for the gemini content view.
+/// Used for gemtext as well as for other formats converted to gemtext.
+/// Links for which `visited` returns true are styled as visited.
+///
+/// `alt_texts` holds the alt text of each preformatted node. The display
+/// state of preformatted blocks is kept in `blocks`, new blocks are
+/// added folded if they have an alt text.
+pub fn render(
+    mut nodes: Vec<gemtext::Node>,
+    alt_texts: &[String],
+    base_url: &Url,
+    viewport_width: usize,
+    visited: &dyn Fn(&Url) -> bool,
+    blocks: &mut Vec<PreformattedBlock>,
+) -> Vec<(StyledString, Option<Url>)> {
+    let styles = SETTINGS.read().unwrap().get_gemini_styles();
+    let highlighting = SETTINGS.read().unwrap().config.syntax_highlighting;
+    let style = |role: Role| styles.get(role.name()).copied().unwrap_or_default();
+    let styled = |role: Role, text: String| StyledString::styled(text, style(role));
+    let mut alt_texts = alt_texts.iter();
+    let mut block_index = 0;
+    let mut row = 0;
+
     nodes
         .drain(..)
-        .flat_map(|node: gemtext::Node| -> Vec<(String, Option<Url>)> {
+        .flat_map(|node: gemtext::Node| -> Vec<(StyledString, Option<Url>)> {
             use gemtext::Node;
 
             // Helper function to wrap lines if necessary while indicating that they are continuations like this
@@ -22,33 +239,36 @@ pub fn parse(text: &str, base_url: &Url, viewport_width: usize) -> Vec<(String,
             //     |  goes over
             //     \  multiple lines
             // ```
-            let continuation_lines = |first_prefix, text: &str, url: Option<Url>| {
-                let lines = make_lines(if text.is_empty() { " " } else { text }, viewport_width);
-                lines
-                    .iter()
-                    .enumerate()
-                    .map(|(i, row)| {
-                        let prefix = match i {
-                            0 => first_prefix,
-                            x if x == lines.len() - 1 => "\\",
-                            _ => "|",
-                        };
-
-                        (
-                            format!("{:>5}  {}", prefix, &text[row.start..row.end]),
-                            url.clone(),
-                        )
-                    })
-                    .collect()
-            };
+            let continuation_lines =
+                |first_prefix, prefix_role, text: &str, text_role, url: Option<Url>| {
+                    let lines =
+                        make_lines(if text.is_empty() { " " } else { text }, viewport_width);
+                    lines
+                        .iter()
+                        .enumerate()
+                        .map(|(i, row)| {
+                            let prefix = match i {
+                                0 => first_prefix,
+                                x if x == lines.len() - 1 => "\\",
+                                _ => "|",
+                            };
+                            let mut line = styled(prefix_role, format!("{:>5}  ", prefix));
+                            line.append(styled(text_role, text[row.start..row.end].to_string()));
+                            (line, url.clone())
+                        })
+                        .collect()
+                };
 
-            match node {
+            let lines: Vec<_> = match node {
                 Node::Text(text) => {
                     let text = if text.is_empty() { " " } else { &text };
                     // Do not use continuation_lines here because text lines
                     // should continue without special markup.
                     LinesIterator::new(text, viewport_width)
-                        .map(|row| (format!("       {}", &text[row.start..row.end]), None))
+                        .map(|row| {
+                            let line = format!("       {}", &text[row.start..row.end]);
+                            (styled(Role::Text, line), None)
+                        })
                         .collect()
                 }
                 Node::Link { to, name } => {
@@ -64,40 +284,108 @@ pub fn parse(text: &str, base_url: &Url, viewport_width: usize) -> Vec<(String,
                             // show first three letters of scheme, lower case to differentiate
                             other => format!("[{}]", other.chars().take(3).collect::<String>()),
                         };
+                        let role = if visited(&url) {
+                            Role::VisitedLink
+                        } else {
+                            Role::link(&url)
+                        };
 
                         // transform the URL into a human redable form
                         // escaping (by parsing as a URL) and unescaping is necessary because
                         // the URL might have been escaped by the author
                         let name = name.unwrap_or_else(|| human_readable_url(&url));
-                        continuation_lines(&prefix, &name, Some(url))
+                        continuation_lines(&prefix, role, &name, role, Some(url))
                     } else {
                         // broken link
                         let mut name = name.unwrap_or_default();
                         name.push_str(&format!(" ?URL? {}", to));
-                        continuation_lines("?URL?", &name, None)
+                        continuation_lines("?URL?", Role::BrokenLink, &name, Role::BrokenLink, None)
                     }
                 }
                 Node::Heading { level, body } => {
                     let text = if body.is_empty() { " " } else { &body };
-                    continuation_lines(&"#".repeat(level as usize), text, None)
+                    let role = Role::heading(level);
+                    continuation_lines(&"#".repeat(level as usize), role, text, role, None)
                 }
                 Node::Quote(text) => {
                     let text = if text.is_empty() { " " } else { &text };
                     // Do not use continuation_lines here because quote lines
                     // are simply rewrapped and then handled like text.
                     LinesIterator::new(text, viewport_width)
-                        .map(|row| (format!("    >  {}", &text[row.start..row.end]), None))
+                        .map(|row| {
+                            let line = format!("    >  {}", &text[row.start..row.end]);
+                            (styled(Role::Quote, line), None)
+                        })
                         .collect()
                 }
-                Node::ListItem(text) => continuation_lines("*", &text, None),
-                Node::Preformatted(lines) => {
-                    // preformatted lines should not be wrapped
+                Node::ListItem(text) => {
+                    continuation_lines("*", Role::ListBullet, &text, Role::Text, None)
+                }
+                Node::Preformatted(text) => {
+                    let alt = alt_texts.next().cloned().unwrap_or_default();
+                    if blocks.len() <= block_index {
+                        blocks.push(PreformattedBlock {
+                            folded: !alt.is_empty(),
+                            alt: alt.clone(),
+                            ..Default::default()
+                        });
+                    }
+                    let block = &mut blocks[block_index];
+                    block_index += 1;
+
+                    let mut lines = Vec::new();
+                    if !alt.is_empty() {
+                        let marker = if block.folded { "[+]" } else { "[-]" };
+                        let caption = format!("    @  {} {}", marker, alt);
+                        lines.push((styled(Role::PreformattedCaption, caption), None));
+                    }
+                    if !block.folded {
+                        // preformatted lines should not be wrapped, but may
+                        // contain ANSI colors spanning multiple lines. Wide
+                        // blocks are scrolled horizontally instead.
+                        let code = if highlighting && !crate::ansi::has_escapes(&text) {
+                            crate::highlight::highlight(&alt, &text)
+                        } else {
+                            None
+                        };
+                        let content = match code {
+                            Some(pieces) => {
+                                let mut content = StyledString::new();
+                                for (piece, token) in pieces {
+                                    match token {
+                                        Some(token) => {
+                                            content.append_styled(piece, style(Role::code(token)))
+                                        }
+                                        None => content.append_plain(piece),
+                                    }
+                                }
+                                content
+                            }
+                            None => crate::ansi::render(&text),
+                        };
+                        let rows = crate::ansi::wrap(&content, usize::MAX);
+                        let width = rows.iter().map(|row| row.width()).max().unwrap_or(0);
+                        block.offset = block.offset.min(width.saturating_sub(viewport_width));
+                        let base = style(Role::Preformatted);
+                        let prefix = if block.offset > 0 {
+                            "    <  "
+                        } else {
+                            "    @  "
+                        };
+                        for line in rows {
+                            let mut formatted = StyledString::styled(prefix, base);
+                            for span in skip_columns(&line, block.offset).spans() {
+                                formatted.append_styled(span.content, base.combine(*span.attr));
+                            }
+                            lines.push((formatted, None));
+                        }
+                    }
+                    block.rows = row..row + lines.len();
                     lines
-                        .lines()
-                        .map(|line| (format!("    @  {}", line), None))
-                        .collect()
                 }
-            }
+            };
+            row += lines.len();
+            lines
         })
         .collect::<Vec<_>>()
 }
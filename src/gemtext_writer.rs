@@ -0,0 +1,238 @@
+use gemtext::Node;
+
+// Builds gemtext nodes for the converters of other markup languages
+// (see html.rs and markdown.rs). The converters push inline text and
+// open and close blocks, the writer turns each block into nodes. Links
+// are numbered in the text and listed as link lines after each block.
+
+#[derive(Default)]
+pub struct GemtextWriter {
+    nodes: Vec<Node>,
+    /// Inline text of the current block
+    text: String,
+    /// Links found in the current block (target, name)
+    links: Vec<(String, String)>,
+    /// Number of links seen in the document
+    link_count: usize,
+    /// Target and text of the currently open link
+    link: Option<(String, String)>,
+    /// Heading level of the currently open heading
+    heading: Option<u8>,
+    /// Nesting of block quotes
+    quote_depth: usize,
+    /// Nesting of preformatted blocks
+    pre_depth: usize,
+    /// Alt text of the current preformatted block
+    alt: String,
+    /// Alt texts of the preformatted nodes
+    alt_texts: Vec<String>,
+    /// Counters of open lists, None for unordered lists
+    lists: Vec<Option<usize>>,
+    /// Set when the current block is the first line of a list item
+    list_item: bool,
+}
+
+impl GemtextWriter {
+    pub fn new() -> GemtextWriter {
+        GemtextWriter::default()
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        self.text.push_str(text);
+        if let Some((_, name)) = self.link.as_mut() {
+            name.push_str(text);
+        }
+    }
+
+    /// Adds text with whitespace collapsed like a browser does, except
+    /// in preformatted blocks
+    pub fn push_collapsed(&mut self, text: &str) {
+        if self.pre_depth > 0 {
+            self.push_text(text);
+            return;
+        }
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                self.text.push(c);
+            } else if !self.text.is_empty() && !self.text.ends_with([' ', '\n']) {
+                self.text.push(' ');
+            }
+        }
+        if let Some((_, name)) = self.link.as_mut() {
+            name.push_str(text);
+        }
+    }
+
+    pub fn line_break(&mut self) {
+        self.text.push('\n');
+    }
+
+    /// Separates the cells of a table row
+    pub fn table_cell(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push_str(" | ");
+        }
+    }
+
+    /// Ends the current block and emits its text and links as nodes
+    pub fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        if self.pre_depth > 0 {
+            let text = text.trim_matches('\n');
+            if !text.is_empty() {
+                self.nodes.push(Node::Preformatted(text.to_string()));
+                self.alt_texts.push(self.alt.clone());
+            }
+        } else {
+            for line in text.split('\n') {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let node = if let Some(level) = self.heading {
+                    Node::Heading {
+                        level,
+                        body: line.to_string(),
+                    }
+                } else if self.list_item {
+                    // nested lists are indented
+                    let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                    let body = match self.lists.last() {
+                        Some(Some(counter)) => format!("{}{}. {}", indent, counter, line),
+                        _ => format!("{}{}", indent, line),
+                    };
+                    Node::ListItem(body)
+                } else if self.quote_depth > 0 {
+                    Node::Quote(line.to_string())
+                } else {
+                    Node::Text(line.to_string())
+                };
+                self.nodes.push(node);
+                // continuation lines of a list item are plain text
+                self.list_item = false;
+            }
+        }
+        for (to, name) in self.links.drain(..) {
+            self.nodes.push(Node::Link {
+                to,
+                name: Some(name),
+            });
+        }
+    }
+
+    /// Adds an empty line unless the document is empty or already
+    /// ends with one
+    pub fn blank_line(&mut self) {
+        if let Some(last) = self.nodes.last() {
+            if *last != Node::Text(String::new()) {
+                self.nodes.push(Node::Text(String::new()));
+            }
+        }
+    }
+
+    pub fn start_heading(&mut self, level: u8) {
+        self.flush();
+        self.blank_line();
+        // gemtext only knows three heading levels
+        self.heading = Some(level.min(3));
+    }
+
+    pub fn end_heading(&mut self) {
+        self.flush();
+        self.heading = None;
+    }
+
+    pub fn start_quote(&mut self) {
+        self.flush();
+        self.quote_depth += 1;
+    }
+
+    pub fn end_quote(&mut self) {
+        self.flush();
+        self.quote_depth = self.quote_depth.saturating_sub(1);
+    }
+
+    pub fn start_preformatted(&mut self, alt: &str) {
+        self.flush();
+        self.pre_depth += 1;
+        self.alt = alt.to_string();
+    }
+
+    pub fn end_preformatted(&mut self) {
+        self.flush();
+        self.pre_depth = self.pre_depth.saturating_sub(1);
+    }
+
+    /// Opens a list, numbered from `start` if given
+    pub fn start_list(&mut self, start: Option<usize>) {
+        self.flush();
+        if self.lists.is_empty() {
+            self.blank_line();
+        }
+        self.lists.push(start.map(|s| s.saturating_sub(1)));
+    }
+
+    pub fn end_list(&mut self) {
+        self.flush();
+        self.lists.pop();
+        self.list_item = false;
+    }
+
+    pub fn in_list(&self) -> bool {
+        !self.lists.is_empty()
+    }
+
+    pub fn start_item(&mut self) {
+        self.flush();
+        if let Some(Some(counter)) = self.lists.last_mut() {
+            *counter += 1;
+        }
+        self.list_item = true;
+    }
+
+    pub fn end_item(&mut self) {
+        self.flush();
+        self.list_item = false;
+    }
+
+    pub fn start_link(&mut self, to: &str) {
+        self.link = Some((to.to_string(), String::new()));
+    }
+
+    /// Numbers the open link in the text and lists it after the block.
+    /// The name of the link line starts with `prefix`.
+    pub fn end_link(&mut self, prefix: &str) {
+        if let Some((to, name)) = self.link.take() {
+            self.link_count += 1;
+            let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+            let name = if name.is_empty() { to.clone() } else { name };
+            self.text.push_str(&format!("[{}]", self.link_count));
+            self.links
+                .push((to, format!("[{}] {}{}", self.link_count, prefix, name)));
+        }
+    }
+
+    /// Adds the alt text of an image and numbers it like a link. Other
+    /// than `start_link` this works inside of an open link.
+    pub fn image(&mut self, src: &str, alt: &str) {
+        self.link_count += 1;
+        self.push_text(&format!("{}[{}]", alt, self.link_count));
+        self.links.push((
+            src.to_string(),
+            format!("[{}] Image: {}", self.link_count, alt),
+        ));
+    }
+
+    /// Adds a horizontal rule
+    pub fn rule(&mut self) {
+        self.flush();
+        self.nodes.push(Node::Text("-".repeat(20)));
+    }
+
+    /// Ends the document and returns its nodes and the alt texts of
+    /// its preformatted nodes
+    pub fn finish(mut self) -> (Vec<Node>, Vec<String>) {
+        self.flush();
+        (self.nodes, self.alt_texts)
+    }
+}
@@ -1,4 +1,4 @@
-use regex::Regex;
+use cursive::utils::markup::StyledString;
 use url::Url;
 
 /// An menu item in a directory of Gopher resources.
@@ -7,7 +7,7 @@ use url::Url;
 pub struct GopherMapEntry {
     /// The type of the resource
     pub item_type: ItemType,
-    /// String to display to the user.
+    /// String to display to the user. May contain ANSI escape sequences.
     pub name: String,
     /// Path or identifier used for requesting this resource.
     pub selector: String,
@@ -17,6 +17,20 @@ pub struct GopherMapEntry {
     pub port: u16,
     /// The combined URL of host, port and selector
     pub url: Url,
+    /// Alternative servers for this resource, given by redundant
+    /// server (+) lines following the entry
+    pub mirrors: Vec<Url>,
+}
+
+/// Returns the first info line containing text, which is used as
+/// title of the gophermap. Lines of ASCII art are skipped.
+pub fn title(gophermap: &str) -> Option<String> {
+    gophermap
+        .lines()
+        .filter_map(|line| GopherMapEntry::parse(line.to_string()).ok())
+        .filter(|entry| entry.item_type.is_inline())
+        .map(|entry| entry.label().trim().to_string())
+        .find(|label| label.chars().any(char::is_alphanumeric))
 }
 
 impl GopherMapEntry {
@@ -33,6 +47,7 @@ impl GopherMapEntry {
                 host: "about:blank".to_string(),
                 port: 70,
                 url: Url::parse("about:blank").unwrap(),
+                mirrors: Vec::new(),
             });
         }
         if l.is_empty() {
@@ -45,11 +60,9 @@ impl GopherMapEntry {
         let ch = l[0].chars().next().unwrap();
         let item_type = ItemType::decode(ch);
 
+        // ANSI sequences (baud.baby, I'm looking at you) are kept here
+        // and rendered or stripped by label() and styled_label()
         let mut name = l[0][ch.len_utf8()..].to_string();
-
-        // Remove ANSI sequences. baud.baby, I'm looking at you
-        let ansi_sequences = Regex::new(r"(\x9B|\x1B\[)[0-?]*[ -/]*[@-~]").unwrap();
-        name = ansi_sequences.replace_all(name.as_str(), "").to_string();
         if name.ends_with("\r") {
             name.pop();
         }
@@ -68,6 +81,7 @@ impl GopherMapEntry {
                 host,
                 port,
                 url,
+                mirrors: Vec::new(),
             });
         } else {
             if l.len() <= 3 {
@@ -89,15 +103,14 @@ impl GopherMapEntry {
                 url.set_host(Some(host.as_str())).unwrap();
             }
             url.set_port(Some(port)).unwrap();
-        } else if item_type == ItemType::Html {
-            if path.starts_with("hURL:") {
-                let mut html_url = path;
-                html_url.replace_range(..5, "");
-                match Url::parse(html_url.as_str()) {
-                    Ok(u) => url = u,
-                    Err(e) => {
-                        warn!("Could not parse url {}: {}", e, html_url);
-                    }
+        } else if item_type == ItemType::Html && path.starts_with("hURL:") {
+            // Link to an external web page
+            let mut html_url = path;
+            html_url.replace_range(..5, "");
+            match Url::parse(html_url.as_str()) {
+                Ok(u) => url = u,
+                Err(e) => {
+                    warn!("Could not parse url {}: {}", e, html_url);
                 }
             }
         } else {
@@ -117,11 +130,39 @@ impl GopherMapEntry {
             host,
             port,
             url,
+            mirrors: Vec::new(),
         })
     }
 
     pub fn label(self) -> String {
-        self.name
+        crate::ansi::strip(&self.name)
+    }
+
+    /// The label with ANSI colors, if enabled in the settings
+    pub fn styled_label(&self) -> StyledString {
+        crate::ansi::render(&self.name)
+    }
+
+    /// Adds a redundant server (+) line as a mirror of this entry.
+    ///
+    /// According to RFC 1436 the redundant server provides the same
+    /// resource as the item above it, so the mirror URL uses the item
+    /// type of this entry with host, port and selector of the mirror.
+    pub fn add_mirror(&mut self, mirror: &GopherMapEntry) {
+        if self.url.scheme() != "gopher" {
+            // Telnet sessions and web links cannot be mirrored
+            return;
+        }
+        let mut url = self.url.clone();
+        if url.set_host(Some(mirror.host.as_str())).is_err() {
+            warn!("Invalid host for redundant server: {}", mirror.host);
+            return;
+        }
+        url.set_port(Some(mirror.port)).unwrap();
+        let mut path = mirror.selector.clone();
+        path.insert(0, self.item_type.encode());
+        url.set_path(path.as_str());
+        self.mirrors.push(url);
     }
 }
 
@@ -206,6 +247,34 @@ impl ItemType {
         }
     }
 
+    /// Returns the character used for this item type in gophermaps
+    pub fn encode(self) -> char {
+        match self {
+            ItemType::File => '0',
+            ItemType::Dir => '1',
+            ItemType::CsoServer => '2',
+            ItemType::Error => '3',
+            ItemType::BinHex => '4',
+            ItemType::Dos => '5',
+            ItemType::Uuencoded => '6',
+            ItemType::IndexServer => '7',
+            ItemType::Telnet => '8',
+            ItemType::Binary => '9',
+            ItemType::RedundantServer => '+',
+            ItemType::Tn3270 => 'T',
+            ItemType::Gif => 'g',
+            ItemType::Image => 'I',
+            ItemType::Html => 'h',
+            ItemType::Document => 'd',
+            ItemType::Video => ';',
+            ItemType::Mime => 'M',
+            ItemType::Calendar => 'c',
+            ItemType::Sound => 's',
+            ItemType::Inline => 'i',
+            ItemType::Other(ch) => ch,
+        }
+    }
+
     pub fn as_str(item_type: ItemType) -> String {
         match item_type {
             ItemType::File => "[TXT]",
@@ -251,6 +320,12 @@ impl ItemType {
         )
     }
 
+    /// Returns true for downloads that are transferred in an encoded
+    /// text format and have to be decoded after download
+    pub fn is_encoded(self) -> bool {
+        matches!(self, ItemType::BinHex | ItemType::Uuencoded)
+    }
+
     pub fn is_text(self) -> bool {
         matches!(self, ItemType::File)
     }
@@ -279,6 +354,14 @@ impl ItemType {
         matches!(self, ItemType::Html)
     }
 
+    pub fn is_cso(self) -> bool {
+        matches!(self, ItemType::CsoServer)
+    }
+
+    pub fn is_redundant_server(self) -> bool {
+        matches!(self, ItemType::RedundantServer)
+    }
+
     /// Returns the ItemType of an url. Defaults to gophermap (ItemType::Dir 1)
     pub fn from_url(url: &Url) -> ItemType {
         let path = url.path();
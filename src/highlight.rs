@@ -0,0 +1,376 @@
+// Simple syntax highlighting for preformatted code blocks. The
+// language is taken from the alt text of the block.
+
+/// Kinds of highlighted tokens
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+struct Language {
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    string_delimiters: &'static [char],
+    /// Single quotes are used for character literals and lifetimes,
+    /// not for strings
+    char_literals: bool,
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    char_literals: true,
+};
+
+const C: Language = Language {
+    keywords: &[
+        "auto",
+        "bool",
+        "break",
+        "case",
+        "catch",
+        "char",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "double",
+        "else",
+        "enum",
+        "extern",
+        "false",
+        "float",
+        "for",
+        "goto",
+        "if",
+        "int",
+        "long",
+        "namespace",
+        "new",
+        "nullptr",
+        "private",
+        "protected",
+        "public",
+        "register",
+        "return",
+        "short",
+        "signed",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typedef",
+        "union",
+        "unsigned",
+        "using",
+        "virtual",
+        "void",
+        "volatile",
+        "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    char_literals: true,
+};
+
+const GO: Language = Language {
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "false",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "nil",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "true",
+        "type",
+        "var",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '`'],
+    char_literals: true,
+};
+
+const JAVA: Language = Language {
+    keywords: &[
+        "abstract",
+        "boolean",
+        "break",
+        "byte",
+        "case",
+        "catch",
+        "char",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "do",
+        "double",
+        "else",
+        "enum",
+        "extends",
+        "false",
+        "final",
+        "finally",
+        "float",
+        "for",
+        "if",
+        "implements",
+        "import",
+        "instanceof",
+        "int",
+        "interface",
+        "long",
+        "new",
+        "null",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "short",
+        "static",
+        "super",
+        "switch",
+        "synchronized",
+        "this",
+        "throw",
+        "throws",
+        "true",
+        "try",
+        "void",
+        "volatile",
+        "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"'],
+    char_literals: true,
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "return",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    string_delimiters: &['"', '\'', '`'],
+    char_literals: false,
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    char_literals: false,
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "readonly", "return", "then", "until", "while",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    char_literals: false,
+};
+
+const LUA: Language = Language {
+    keywords: &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in",
+        "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ],
+    line_comments: &["--"],
+    block_comment: None,
+    string_delimiters: &['"', '\''],
+    char_literals: false,
+};
+
+/// Finds the language named by the first word of the alt text
+fn language(alt: &str) -> Option<&'static Language> {
+    let name = alt.split_whitespace().next()?.to_lowercase();
+    match name.as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "c" | "h" | "cpp" | "c++" | "cc" | "cxx" | "hpp" => Some(&C),
+        "go" | "golang" => Some(&GO),
+        "java" | "kotlin" | "kt" => Some(&JAVA),
+        "javascript" | "js" | "typescript" | "ts" | "json" => Some(&JAVASCRIPT),
+        "python" | "py" | "python3" => Some(&PYTHON),
+        "sh" | "bash" | "shell" | "zsh" | "ksh" => Some(&SHELL),
+        "lua" => Some(&LUA),
+        _ => None,
+    }
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Length of the string starting with the delimiter `quote` at the
+/// beginning of `text`, including both delimiters
+fn string_length(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+/// Splits code into pieces with their token kind. Returns None if the
+/// language given by the alt text is not known.
+pub fn highlight<'a>(alt: &str, code: &'a str) -> Option<Vec<(&'a str, Option<Token>)>> {
+    let language = language(alt)?;
+    let mut pieces = Vec::new();
+    let mut plain_start = 0;
+    let mut pos = 0;
+    let mut previous = ' ';
+    while let Some(c) = code[pos..].chars().next() {
+        let rest = &code[pos..];
+        let token = if language.line_comments.iter().any(|l| rest.starts_with(l)) {
+            Some((rest.find('\n').unwrap_or(rest.len()), Token::Comment))
+        } else if let Some((start, end)) = language
+            .block_comment
+            .filter(|(start, _)| rest.starts_with(start))
+        {
+            let length = rest[start.len()..]
+                .find(end)
+                .map_or(rest.len(), |i| start.len() + i + end.len());
+            Some((length, Token::Comment))
+        } else if language.string_delimiters.contains(&c) {
+            Some((string_length(rest, c), Token::String))
+        } else if c == '\'' && language.char_literals {
+            // Only short literals like 'a' or '\n', otherwise this
+            // might be a lifetime
+            let length = string_length(rest, c);
+            if length <= 4 && !rest[..length].contains('\n') && length > 2 {
+                Some((length, Token::String))
+            } else {
+                None
+            }
+        } else if c.is_ascii_digit() && !is_identifier(previous) {
+            let length = rest
+                .find(|c: char| !is_identifier(c) && c != '.')
+                .unwrap_or(rest.len());
+            Some((length, Token::Number))
+        } else if is_identifier(c) && !is_identifier(previous) {
+            let length = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+            if language.keywords.contains(&&rest[..length]) {
+                Some((length, Token::Keyword))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        match token {
+            Some((length, token)) => {
+                if plain_start < pos {
+                    pieces.push((&code[plain_start..pos], None));
+                }
+                pieces.push((&code[pos..pos + length], Some(token)));
+                pos += length;
+                plain_start = pos;
+                previous = code[..pos].chars().next_back().unwrap_or(' ');
+            }
+            None => {
+                pos += c.len_utf8();
+                previous = c;
+            }
+        }
+    }
+    if plain_start < code.len() {
+        pieces.push((&code[plain_start..], None));
+    }
+    Some(pieces)
+}
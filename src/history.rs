@@ -1,7 +1,6 @@
-use ::time::OffsetDateTime;
+use ::time::{OffsetDateTime, UtcOffset};
 use rusqlite::{params, Connection, Result};
-use std::path::PathBuf;
-use std::rc::Rc;
+use std::collections::HashSet;
 use url::Url;
 
 #[derive(Clone, Debug)]
@@ -14,18 +13,172 @@ pub struct HistoryEntry {
     pub position: usize,
 }
 
+/// Page visited in the current session
 #[derive(Clone, Debug)]
+struct SessionNode {
+    entry: HistoryEntry,
+    parent: Option<usize>,
+    /// Child visited last, navigating forward leads there
+    forward: Option<usize>,
+}
+
+/// Line of the session tree as shown in the history tree popup
+#[derive(Clone, Debug)]
+pub struct SessionTreeItem {
+    /// Id of the node, used to jump to it
+    pub id: usize,
+    /// Branches are indented one level more than the page they were
+    /// opened from, linear navigation keeps the level
+    pub depth: usize,
+    pub entry: HistoryEntry,
+    pub current: bool,
+}
+
+/// Pages visited in the current session. Navigating back and opening
+/// another page starts a new branch, so no page gets lost.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    nodes: Vec<SessionNode>,
+    current: Option<usize>,
+}
+
+impl Session {
+    /// Adds a page opened from the current page and makes it current
+    pub fn push(&mut self, entry: HistoryEntry) {
+        let id = self.nodes.len();
+        self.nodes.push(SessionNode {
+            entry,
+            parent: self.current,
+            forward: None,
+        });
+        if let Some(current) = self.current {
+            self.nodes[current].forward = Some(id);
+        }
+        self.current = Some(id);
+    }
+
+    /// Remembers the selected row of the current page
+    pub fn set_position(&mut self, position: usize) {
+        if let Some(current) = self.current {
+            let entry = &mut self.nodes[current].entry;
+            info!(
+                "Session::set_position(): {} {} => {}",
+                entry.url, entry.position, position
+            );
+            entry.position = position;
+        }
+    }
+
+    /// Moves to the page the current page was opened from
+    pub fn back(&mut self) -> Option<HistoryEntry> {
+        let parent = self.nodes[self.current?].parent?;
+        self.current = Some(parent);
+        Some(self.nodes[parent].entry.clone())
+    }
+
+    /// Moves to the page visited last from the current page
+    pub fn forward(&mut self) -> Option<HistoryEntry> {
+        let child = self.nodes[self.current?].forward?;
+        self.current = Some(child);
+        Some(self.nodes[child].entry.clone())
+    }
+
+    /// Makes the node with the id current. Navigating forward from its
+    /// ancestors leads back to it.
+    pub fn jump(&mut self, id: usize) -> Option<HistoryEntry> {
+        self.nodes.get(id)?;
+        let mut child = id;
+        while let Some(parent) = self.nodes[child].parent {
+            self.nodes[parent].forward = Some(child);
+            child = parent;
+        }
+        self.current = Some(id);
+        Some(self.nodes[id].entry.clone())
+    }
+
+    /// Returns the pages from the first page of the session to the last
+    /// page that can be reached by navigating forward, and the index of
+    /// the current page among them
+    pub fn branch(&self) -> (Vec<HistoryEntry>, usize) {
+        let mut entries = Vec::new();
+        let mut node = self.current;
+        while let Some(id) = node {
+            entries.push(self.nodes[id].entry.clone());
+            node = self.nodes[id].parent;
+        }
+        entries.reverse();
+        let current = entries.len().saturating_sub(1);
+        let mut node = self.current.and_then(|id| self.nodes[id].forward);
+        while let Some(id) = node {
+            entries.push(self.nodes[id].entry.clone());
+            node = self.nodes[id].forward;
+        }
+        (entries, current)
+    }
+
+    /// Creates a session from the pages returned by `branch`
+    pub fn from_branch(entries: Vec<HistoryEntry>, current: usize) -> Session {
+        let mut session = Session::default();
+        for entry in entries {
+            session.push(entry);
+        }
+        session.jump(current);
+        session
+    }
+
+    /// Returns all pages of the session in the order they were opened,
+    /// every page followed by the pages opened from it
+    pub fn tree(&self) -> Vec<SessionTreeItem> {
+        let mut roots = Vec::new();
+        let mut children = vec![Vec::new(); self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate() {
+            match node.parent {
+                Some(parent) => children[parent].push(id),
+                None => roots.push(id),
+            }
+        }
+        // Walk the tree with a stack, long sessions would overflow the
+        // call stack when recursing once per page. Nodes are pushed in
+        // reverse so they are taken off the stack in order.
+        let mut items = Vec::new();
+        let mut stack: Vec<(usize, usize)> = roots.into_iter().rev().map(|id| (id, 0)).collect();
+        while let Some((id, depth)) = stack.pop() {
+            items.push(SessionTreeItem {
+                id,
+                depth,
+                entry: self.nodes[id].entry.clone(),
+                current: self.current == Some(id),
+            });
+            let children = &children[id];
+            let depth = if children.len() > 1 { depth + 1 } else { depth };
+            stack.extend(children.iter().rev().map(|&child| (child, depth)));
+        }
+        items
+    }
+
+    fn set_title(&mut self, url: &Url, title: &str) {
+        for node in self.nodes.iter_mut().filter(|n| &n.entry.url == url) {
+            node.entry.title = title.to_string();
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct History {
-    /// Navigational stack, used for back-functionality
-    pub stack: Vec<HistoryEntry>,
+    /// Pages visited in this session, used for back and forward
+    /// navigation
+    pub session: Session,
     /// Log of all visited gopherholes
-    sql: Rc<Connection>,
+    sql: Connection,
+    /// URLs in the log, kept in memory to style the links of every
+    /// rendered page without a query per link
+    visited: HashSet<String>,
 }
 
 impl History {
     pub fn new() -> Result<Self> {
         info!("Creating history object");
-        let connection = Rc::new(Connection::open(History::get_history_filename())?);
+        let connection = crate::database::open("history.db")?;
         connection.execute(
             "CREATE TABLE IF NOT EXISTS history (
              id INTEGER PRIMARY KEY,
@@ -36,77 +189,89 @@ impl History {
          )",
             [],
         )?;
+        connection.execute(
+            "CREATE INDEX IF NOT EXISTS history_url ON history (url)",
+            [],
+        )?;
+        let visited = History::load_visited(&connection)?;
         Ok(History {
-            stack: Vec::new(),
+            session: Session::default(),
             sql: connection,
+            visited,
         })
     }
 
-    fn get_history_filename() -> PathBuf {
-        let mut dir = dirs::config_dir().expect("no configuration directory");
-        dir.push(env!("CARGO_PKG_NAME"));
-        dir.push("history.db");
-        dir
+    fn load_visited(sql: &Connection) -> Result<HashSet<String>> {
+        let mut stmt = sql.prepare("SELECT url FROM history")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
     }
 
     pub fn add(&mut self, entry: HistoryEntry) -> Result<()> {
         info!("Adding entry to history: {:?}", entry);
-        self.stack.push(entry.clone());
+        self.session.push(entry.clone());
+        self.record(&entry.url)
+    }
 
-        trace!("History::add(): checking for entry with url {}", entry.url);
+    /// Logs a visit of the URL without changing the session
+    pub fn record(&mut self, url: &Url) -> Result<()> {
+        trace!("History::record(): checking for entry with url {}", url);
         if self
             .sql
             .query_row(
                 "SELECT id FROM history WHERE url=?1",
-                params![&entry.url.to_string()],
+                params![&url.to_string()],
                 |_| Ok(()),
             )
             .is_ok()
         {
-            trace!("History::add(): Row exists, updating");
+            trace!("History::record(): Row exists, updating");
             let mut stmt = self
                 .sql
                 .prepare("UPDATE history SET visitedcount=visitedcount+1,timestmp=datetime('NOW') WHERE url=?1")?;
-            stmt.execute(params![&entry.url.to_string()])?;
+            stmt.execute(params![&url.to_string()])?;
         } else {
-            trace!("History::add(): Adding entry");
-            self.sql.execute(
-                "INSERT INTO history (url) values (?1)",
-                [&entry.url.to_string()],
-            )?;
+            trace!("History::record(): Adding entry");
+            self.sql
+                .execute("INSERT INTO history (url) values (?1)", [&url.to_string()])?;
+            self.visited.insert(url.to_string());
         }
         Ok(())
     }
 
+    /// Returns true if the URL is in the history
+    pub fn is_visited(&self, url: &Url) -> bool {
+        self.visited.contains(url.as_str())
+    }
+
+    /// Stores the title of a page. Returns true if the title changed.
+    pub fn set_title(&mut self, url: &Url, title: &str) -> Result<bool> {
+        self.session.set_title(url, title);
+        let changed = self.sql.execute(
+            "UPDATE history SET title=?2 WHERE url=?1 AND title IS NOT ?2",
+            params![&url.to_string(), title],
+        )?;
+        Ok(changed > 0)
+    }
+
     pub fn clear(&mut self) -> Result<()> {
         trace!("History::clear()");
-        self.stack.clear();
+        self.session = Session::default();
         self.sql.execute("DELETE FROM history", [])?;
+        self.visited.clear();
         Ok(())
     }
 
-    pub fn back(&mut self) -> Option<HistoryEntry> {
-        // Removes the topmost entry from the history and returns it
-        if self.stack.len() > 1 {
-            self.stack.pop();
-            Some(self.stack.last()?.clone())
-        } else {
-            None
-        }
-    }
-
-    pub fn update_selected_item(&mut self, index: usize) {
-        // Updates the current selection position of the history item
-        // on top of the stack
-        if !self.stack.is_empty() {
-            let mut item = self.stack.pop().expect("Could not fetch history item");
-            info!(
-                "update_selected_item(): {} {} => {}",
-                item.url, item.position, index
-            );
-            item.position = index;
-            self.stack.push(item);
-        }
+    /// Removes the entries that were last visited more than `hours`
+    /// ago. Returns the number of removed entries.
+    pub fn clear_older_than(&mut self, hours: u32) -> Result<usize> {
+        trace!("History::clear_older_than({})", hours);
+        let removed = self.sql.execute(
+            "DELETE FROM history WHERE timestmp < datetime('now', ?1)",
+            params![format!("-{} hours", hours)],
+        )?;
+        self.visited = History::load_visited(&self.sql)?;
+        Ok(removed)
     }
 
     pub fn get_latest_history(&self, num_items: usize) -> Result<Vec<HistoryEntry>> {
@@ -118,10 +283,11 @@ impl History {
             )?;
         let mut rows = stmt.query(params![num_items as u32])?;
         while let Some(row) = rows.next()? {
-            let title = row.get(1)?;
+            let url: Url = row.get(1)?;
+            let title: Option<String> = row.get(0)?;
             let entry = HistoryEntry {
-                title,
-                url: row.get(1)?,
+                title: title.unwrap_or_else(|| url.to_string()),
+                url,
                 timestamp: row.get(2)?,
                 visited_count: row.get(3)?,
                 position: 0,
@@ -132,3 +298,35 @@ impl History {
         Ok(res)
     }
 }
+
+/// Renders the about:history page listing the entries grouped by the
+/// day of their last visit
+pub fn page(entries: &[HistoryEntry]) -> String {
+    let mut page = String::from("# History\n");
+    if entries.is_empty() {
+        page.push_str("\nYour history is empty.\n");
+        return page;
+    }
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    let mut day = None;
+    for entry in entries {
+        let timestamp = entry.timestamp.to_offset(offset);
+        if day != Some(timestamp.date()) {
+            day = Some(timestamp.date());
+            page.push_str(&format!("\n## {}\n\n", timestamp.date()));
+        }
+        let visits = match entry.visited_count {
+            1 => String::new(),
+            count => format!(" ({} visits)", count),
+        };
+        page.push_str(&format!(
+            "=> {} {:02}:{:02} {}{}\n",
+            entry.url,
+            timestamp.hour(),
+            timestamp.minute(),
+            entry.title,
+            visits
+        ));
+    }
+    page
+}
@@ -0,0 +1,303 @@
+use crate::gemtext_writer::GemtextWriter;
+use gemtext::Node;
+
+// A lightweight HTML to text converter. HTML is mapped onto gemtext
+// nodes so it can be rendered like any other gemini page.
+
+/// Elements whose content is never shown
+const HIDDEN_ELEMENTS: &[&str] = &[
+    "script", "style", "head", "noscript", "template", "svg", "iframe",
+];
+
+/// Elements that start a new block of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "nav",
+    "main",
+    "aside",
+    "address",
+    "figure",
+    "figcaption",
+    "form",
+    "fieldset",
+    "table",
+    "tr",
+    "dl",
+    "dt",
+    "dd",
+    "ul",
+    "ol",
+    "body",
+    "html",
+    "center",
+    "details",
+    "summary",
+];
+
+#[derive(Default)]
+struct Converter {
+    writer: GemtextWriter,
+    /// Set while an <a> element with a followable target is open
+    in_anchor: bool,
+    /// Nesting of hidden elements like <script>
+    hidden_depth: usize,
+    /// Text of the <title> element
+    title: Option<String>,
+    in_title: bool,
+}
+
+impl Converter {
+    fn push_text(&mut self, text: &str) {
+        if self.in_title {
+            self.title.get_or_insert_with(String::new).push_str(text);
+            return;
+        }
+        if self.hidden_depth > 0 {
+            return;
+        }
+        self.writer.push_collapsed(text);
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &str) {
+        if HIDDEN_ELEMENTS.contains(&name) {
+            self.hidden_depth += 1;
+            return;
+        }
+        let writer = &mut self.writer;
+        match name {
+            "title" => self.in_title = true,
+            "br" => writer.line_break(),
+            "a" => {
+                if let Some(href) = attribute(attributes, "href") {
+                    if !href.starts_with('#') && !href.starts_with("javascript:") {
+                        writer.start_link(&href);
+                        self.in_anchor = true;
+                    }
+                }
+            }
+            "img" => {
+                let alt = attribute(attributes, "alt").unwrap_or_default();
+                if let Some(src) = attribute(attributes, "src") {
+                    let alt = if alt.is_empty() { "image".into() } else { alt };
+                    writer.image(&src, &alt);
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                writer.start_heading(name[1..].parse::<u8>().unwrap_or(1));
+            }
+            "pre" => writer.start_preformatted(""),
+            "blockquote" => writer.start_quote(),
+            "ul" | "menu" => writer.start_list(None),
+            "ol" => {
+                let start = attribute(attributes, "start")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1);
+                writer.start_list(Some(start));
+            }
+            "li" => writer.start_item(),
+            "hr" => writer.rule(),
+            "td" | "th" => writer.table_cell(),
+            "p" => {
+                writer.flush();
+                writer.blank_line();
+            }
+            other if BLOCK_ELEMENTS.contains(&other) => writer.flush(),
+            _ => (),
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        if HIDDEN_ELEMENTS.contains(&name) {
+            self.hidden_depth = self.hidden_depth.saturating_sub(1);
+            return;
+        }
+        let writer = &mut self.writer;
+        match name {
+            "title" => self.in_title = false,
+            "a" if self.in_anchor => {
+                writer.end_link("");
+                self.in_anchor = false;
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => writer.end_heading(),
+            "pre" => writer.end_preformatted(),
+            "blockquote" => writer.end_quote(),
+            "ul" | "ol" | "menu" => writer.end_list(),
+            "li" => writer.end_item(),
+            "p" => {
+                writer.flush();
+                writer.blank_line();
+            }
+            other if BLOCK_ELEMENTS.contains(&other) => writer.flush(),
+            _ => (),
+        }
+    }
+}
+
+/// Converts an HTML document into gemtext nodes
+pub fn parse(html: &str) -> Vec<Node> {
+    let mut converter = Converter::default();
+    let mut rest = html;
+    while !rest.is_empty() {
+        match rest.find('<') {
+            Some(0) => {
+                if let Some(comment) = rest.strip_prefix("<!--") {
+                    // skip comments
+                    rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                    continue;
+                }
+                let end = match rest.find('>') {
+                    Some(end) => end,
+                    None => {
+                        converter.push_text(&decode_entities(rest));
+                        break;
+                    }
+                };
+                let tag = &rest[1..end];
+                rest = &rest[end + 1..];
+                if let Some(name) = tag.strip_prefix('/') {
+                    converter.end_tag(&name.trim().to_lowercase());
+                } else if !tag.starts_with('!') && !tag.starts_with('?') {
+                    let tag = tag.trim_end_matches('/');
+                    let (name, attributes) = tag
+                        .split_once(|c: char| c.is_whitespace())
+                        .unwrap_or((tag, ""));
+                    let name = name.to_lowercase();
+                    converter.start_tag(&name, attributes);
+                    if name == "br" || name == "img" || name == "hr" {
+                        continue;
+                    }
+                    if name != "head" && HIDDEN_ELEMENTS.contains(&name.as_str()) {
+                        // Scripts and styles may contain '<', so skip
+                        // to the closing tag directly
+                        let closing = format!("</{}", name);
+                        if let Some(pos) = rest.to_ascii_lowercase().find(&closing) {
+                            rest = &rest[pos..];
+                        }
+                    }
+                }
+            }
+            Some(pos) => {
+                converter.push_text(&decode_entities(&rest[..pos]));
+                rest = &rest[pos..];
+            }
+            None => {
+                converter.push_text(&decode_entities(rest));
+                break;
+            }
+        }
+    }
+    let (mut body, _) = converter.writer.finish();
+
+    let mut nodes = Vec::new();
+    if let Some(title) = converter.title.take() {
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !title.is_empty() {
+            nodes.push(Node::Heading {
+                level: 1,
+                body: title,
+            });
+        }
+    }
+    nodes.append(&mut body);
+    nodes
+}
+
+/// Extracts the (entity decoded) value of an attribute from the
+/// attribute part of a tag
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim().rsplit(char::is_whitespace).next()?;
+        let value = rest[eq + 1..].trim_start();
+        let (val, remaining) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value[1..];
+                let end = value.find(quote).unwrap_or(value.len());
+                (&value[..end], value.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(val));
+        }
+        rest = remaining;
+    }
+    None
+}
+
+/// Replaces character references like `&amp;` or `&#x2014;`
+pub fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = match rest.find(';') {
+            // entities are short, do not look too far
+            Some(end) if end < 10 => end,
+            _ => {
+                result.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let decoded = if let Some(hex) = entity
+            .strip_prefix("#x")
+            .or_else(|| entity.strip_prefix("#X"))
+        {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else if let Some(dec) = entity.strip_prefix('#') {
+            dec.parse::<u32>().ok().and_then(char::from_u32)
+        } else {
+            match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                "shy" => Some('\u{ad}'),
+                "copy" => Some('©'),
+                "reg" => Some('®'),
+                "trade" => Some('™'),
+                "hellip" => Some('…'),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "lsquo" => Some('‘'),
+                "rsquo" => Some('’'),
+                "ldquo" => Some('“'),
+                "rdquo" => Some('”'),
+                "laquo" => Some('«'),
+                "raquo" => Some('»'),
+                "bull" => Some('•'),
+                "middot" => Some('·'),
+                "deg" => Some('°'),
+                "euro" => Some('€'),
+                "pound" => Some('£'),
+                "times" => Some('×'),
+                _ => None,
+            }
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
@@ -15,25 +15,41 @@ extern crate serde_derive;
 extern crate toml;
 extern crate x509_parser;
 
+use bookmarks::Bookmarks;
 use clap::Parser;
 use controller::Controller;
 use lazy_static::lazy_static;
 use settings::Settings;
 use std::fs::File;
 use std::io::{stdout, Write};
+use std::path::Path;
 use std::sync::RwLock;
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 use url::Url;
 
+mod ansi;
 mod bookmarks;
 mod certificates;
 mod clientcertificates;
 mod controller;
+mod cso;
+mod database;
+mod decode;
+mod feeds;
 mod gemini;
+mod gemtext_writer;
 mod gophermap;
+mod highlight;
 mod history;
+mod html;
+mod markdown;
+mod phlogs;
+mod readinglist;
+mod session;
 mod settings;
+mod sixel;
+mod tabs;
 mod ui;
 mod url_tools;
 
@@ -81,6 +97,11 @@ struct Args {
     #[clap(short, long)]
     debug: Option<String>,
 
+    /// Import the bookmarks of Lagrange, Amfora, Bombadillo or a gemtext
+    /// link list from the specified file and exit
+    #[clap(long, value_name = "FILE")]
+    import_bookmarks: Option<String>,
+
     /// Url to open after startup
     url: Option<String>,
 }
@@ -88,6 +109,20 @@ struct Args {
 fn main() {
     let args = Args::parse();
 
+    if let Some(path) = args.import_bookmarks.as_deref() {
+        match Bookmarks::new().import_file(Path::new(path)) {
+            Ok((added, merged)) => println!(
+                "Imported {} new bookmarks, merged {} known bookmarks",
+                added, merged
+            ),
+            Err(err) => {
+                eprintln!("Could not import bookmarks from {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let homepage = args
         .url
         .as_deref()
@@ -115,17 +150,30 @@ fn main() {
     std::panic::set_hook(Box::new(move |info| {
         // print to log file
         error!("{}\n{:?}", info, backtrace::Backtrace::new());
+        // keep the open tabs so they can be restored
+        if let Err(err) = session::write_snapshot() {
+            error!("Could not write session snapshot: {}", err);
+        }
         // run default hook to print to stdout
         default_hook(info);
     }));
 
     let mut app = cursive::default();
-    let theme = SETTINGS.read().unwrap().config.theme.clone();
-    app.load_toml(SETTINGS.read().unwrap().get_theme_by_name(theme))
-        .unwrap();
+    app.load_toml(SETTINGS.read().unwrap().theme()).unwrap();
     Controller::setup(&mut app, homepage).expect("could not create controller");
+    if args.url.is_none() {
+        ui::dialogs::restore_session(&mut app);
+    }
     // required so async updates to the status bar get shown
-    app.run();
+    let mut runner = app.runner();
+    runner.refresh();
+    while runner.is_running() {
+        runner.step();
+        // sixel images are written over the finished frame
+        ui::imageview::write_sixel();
+    }
+    drop(runner);
+    Controller::save_session(&mut app);
     print!("\x1B[?1002l");
     stdout().flush().expect("could not flush stdout");
     pancurses::endwin();
@@ -0,0 +1,69 @@
+use crate::gemtext_writer::GemtextWriter;
+use gemtext::Node;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+// Converts markdown into gemtext nodes so it can be rendered like any
+// other gemini page. Like in the HTML converter, inline and reference
+// links are numbered in the text and listed as link lines after each
+// block.
+
+/// Converts a markdown document into gemtext nodes and the alt texts
+/// of its preformatted nodes
+pub fn parse(text: &str) -> (Vec<Node>, Vec<String>) {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut writer = GemtextWriter::new();
+    for event in Parser::new_ext(text, options) {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { level, .. } => writer.start_heading(level as u8),
+                Tag::Paragraph if !writer.in_list() => writer.blank_line(),
+                Tag::BlockQuote(_) => writer.start_quote(),
+                Tag::CodeBlock(kind) => {
+                    writer.flush();
+                    writer.blank_line();
+                    writer.start_preformatted(match &kind {
+                        CodeBlockKind::Fenced(lang) => lang.trim(),
+                        CodeBlockKind::Indented => "",
+                    });
+                }
+                Tag::List(start) => writer.start_list(start.map(|s| s as usize)),
+                Tag::Item => writer.start_item(),
+                Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                    writer.start_link(&dest_url)
+                }
+                Tag::TableRow | Tag::TableHead => writer.flush(),
+                Tag::TableCell => writer.table_cell(),
+                _ => (),
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Heading(_) => writer.end_heading(),
+                TagEnd::Paragraph | TagEnd::TableRow | TagEnd::TableHead => writer.flush(),
+                TagEnd::BlockQuote(_) => writer.end_quote(),
+                TagEnd::CodeBlock => writer.end_preformatted(),
+                TagEnd::List(_) => writer.end_list(),
+                TagEnd::Item => writer.end_item(),
+                TagEnd::Link => writer.end_link(""),
+                TagEnd::Image => writer.end_link("Image: "),
+                _ => (),
+            },
+            Event::Text(text) => writer.push_text(&text),
+            Event::Code(code) => writer.push_text(&format!("`{}`", code)),
+            Event::SoftBreak => writer.push_text(" "),
+            Event::HardBreak => writer.line_break(),
+            Event::Rule => writer.rule(),
+            Event::TaskListMarker(checked) => {
+                writer.push_text(if checked { "[x] " } else { "[ ] " })
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                // Only keep the text of embedded HTML
+                let text = html.trim();
+                if !text.starts_with('<') {
+                    writer.push_text(text);
+                }
+            }
+            _ => (),
+        }
+    }
+    writer.finish()
+}
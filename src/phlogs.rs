@@ -0,0 +1,97 @@
+use crate::feeds::{Feed, FeedEntry};
+use crate::gophermap::{GopherMapEntry, ItemType};
+use std::convert::TryFrom;
+use time::Date;
+
+// Change tracking for phlogs. A watched gophermap is a subscription
+// that is refetched periodically, its links are the entries. Gopher
+// has no dates, so they are guessed from the names of the links.
+
+/// Reads a number of exactly `len` digits at the start of the text
+fn number(text: &str, len: usize) -> Option<(u32, &str)> {
+    let digits = text.get(..len)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, &text[len..]))
+}
+
+/// Reads a number of one or two digits at the start of the text
+fn short_number(text: &str) -> Option<(u32, &str)> {
+    number(text, 2).or_else(|| number(text, 1))
+}
+
+fn date(year: u32, month: u32, day: u32, rest: &str) -> Option<Date> {
+    // Digits right after the date belong to something else
+    if rest.starts_with(|c: char| c.is_ascii_digit()) || !(1990..=2100).contains(&year) {
+        return None;
+    }
+    let month = time::Month::try_from(u8::try_from(month).ok()?).ok()?;
+    Date::from_calendar_date(year as i32, month, u8::try_from(day).ok()?).ok()
+}
+
+/// Dates like 2022-01-31, 2022/01/31, 2022.01.31 or 20220131
+fn year_first(text: &str) -> Option<Date> {
+    let (year, rest) = number(text, 4)?;
+    let separator = rest.chars().next().filter(|c| "-/._".contains(*c));
+    let rest = separator.map_or(rest, |s| &rest[s.len_utf8()..]);
+    let (month, rest) = number(rest, 2)?;
+    let rest = match separator {
+        Some(s) => rest.strip_prefix(s)?,
+        None => rest,
+    };
+    let (day, rest) = number(rest, 2)?;
+    date(year, month, day, rest)
+}
+
+/// Dates like 31.01.2022 or 31/1/2022
+fn day_first(text: &str) -> Option<Date> {
+    let (day, rest) = short_number(text)?;
+    let separator = rest.chars().next().filter(|c| "./".contains(*c))?;
+    let (month, rest) = short_number(&rest[1..])?;
+    let (year, rest) = number(rest.strip_prefix(separator)?, 4)?;
+    date(year, month, day, rest)
+}
+
+/// Guesses the date of a phlog entry from its name or selector
+pub fn guess_date(text: &str) -> Option<Date> {
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c.is_ascii_digit() && !previous.is_ascii_digit() {
+            let rest = &text[i..];
+            if let Some(date) = year_first(rest).or_else(|| day_first(rest)) {
+                return Some(date);
+            }
+        }
+        previous = c;
+    }
+    None
+}
+
+/// Returns the links of a gophermap with their guessed dates as feed
+/// entries
+pub fn parse_gophermap(gophermap: &str) -> Feed {
+    let entries = gophermap
+        .lines()
+        .filter(|line| *line != ".")
+        .filter_map(|line| GopherMapEntry::parse(line.to_string()).ok())
+        .filter(|entry| {
+            !matches!(
+                entry.item_type,
+                ItemType::Inline | ItemType::Error | ItemType::RedundantServer
+            )
+        })
+        .map(|entry| {
+            let name = entry.clone().label().trim().to_string();
+            FeedEntry {
+                date: guess_date(&name).or_else(|| guess_date(&entry.selector)),
+                url: entry.url,
+                title: name,
+            }
+        })
+        .collect();
+    Feed {
+        entries,
+        ..Feed::default()
+    }
+}
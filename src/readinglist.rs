@@ -0,0 +1,150 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use url::Url;
+
+// Pages saved to be read later. Every item remembers the position the
+// reader was at and optionally keeps a copy of the page so it can be
+// read offline.
+
+#[derive(Clone, Debug)]
+pub struct ReadingListItem {
+    pub id: i64,
+    pub url: Url,
+    pub title: String,
+    /// First row shown when the item was added
+    pub position: usize,
+    pub read: bool,
+    /// True if a copy of the page is stored
+    pub cached: bool,
+}
+
+/// Stored copy of a page. `kind` tells how the content is rendered,
+/// it is either the name of a gemini type or "gophermap". Other kinds
+/// are rendered according to the gopher item type of the URL.
+#[derive(Clone, Debug)]
+pub struct CachedPage {
+    pub kind: String,
+    pub content: String,
+}
+
+#[derive(Debug)]
+pub struct ReadingList {
+    sql: Connection,
+}
+
+impl ReadingList {
+    pub fn new() -> Result<Self> {
+        info!("Creating reading list object");
+        let connection = crate::database::open("readinglist.db")?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS items (
+             id INTEGER PRIMARY KEY,
+             url TEXT NOT NULL UNIQUE,
+             title TEXT NOT NULL,
+             position INTEGER NOT NULL DEFAULT 0,
+             kind TEXT,
+             content TEXT,
+             added DATETIME DEFAULT CURRENT_TIMESTAMP,
+             read NUMBER NOT NULL DEFAULT 0
+         )",
+            [],
+        )?;
+        Ok(ReadingList { sql: connection })
+    }
+
+    /// Adds a page to the reading list. Adding a page that is already
+    /// in the list updates its position and copy and marks it unread
+    /// again. Returns true if the page was not in the list before.
+    pub fn add(
+        &mut self,
+        url: &Url,
+        title: &str,
+        position: usize,
+        copy: Option<CachedPage>,
+    ) -> Result<bool> {
+        let known = self.contains(url);
+        let (kind, content) = match copy {
+            Some(copy) => (Some(copy.kind), Some(copy.content)),
+            None => (None, None),
+        };
+        self.sql.execute(
+            "INSERT INTO items (url, title, position, kind, content)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (url) DO UPDATE SET
+             position=excluded.position, kind=excluded.kind,
+             content=excluded.content, read=0",
+            params![url.as_str(), title, position as i64, kind, content],
+        )?;
+        Ok(!known)
+    }
+
+    pub fn remove(&mut self, id: i64) -> Result<()> {
+        self.sql
+            .execute("DELETE FROM items WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn contains(&self, url: &Url) -> bool {
+        self.sql
+            .query_row(
+                "SELECT id FROM items WHERE url=?1",
+                params![url.as_str()],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Returns all items, unread items first and newest items first
+    pub fn items(&self) -> Result<Vec<ReadingListItem>> {
+        let mut stmt = self.sql.prepare(
+            "SELECT id, url, title, position, read, content IS NOT NULL
+             FROM items ORDER BY read, added DESC, id DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ReadingListItem {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                title: row.get(2)?,
+                position: row.get::<_, i64>(3)? as usize,
+                read: row.get(4)?,
+                cached: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Returns the stored copy of an item
+    pub fn cached_page(&self, id: i64) -> Result<Option<CachedPage>> {
+        self.sql
+            .query_row(
+                "SELECT kind, content FROM items
+                 WHERE id=?1 AND content IS NOT NULL",
+                params![id],
+                |row| {
+                    Ok(CachedPage {
+                        kind: row.get(0)?,
+                        content: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn set_read(&mut self, id: i64, read: bool) -> Result<()> {
+        self.sql
+            .execute("UPDATE items SET read=?2 WHERE id=?1", params![id, read])?;
+        Ok(())
+    }
+
+    /// Marks the item with the URL as read. Returns true if an unread
+    /// item was marked.
+    pub fn mark_read(&mut self, url: &Url) -> Result<bool> {
+        let changed = self.sql.execute(
+            "UPDATE items SET read=1 WHERE url=?1 AND read=0",
+            params![url.as_str()],
+        )?;
+        Ok(changed > 0)
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use url::Url;
+
+// The open tabs are saved when ncgopher exits and can be restored on
+// the next start. While ncgopher runs, a snapshot of the tabs is kept
+// and written periodically, so the tabs can be restored after a crash.
+
+/// Latest snapshot of the open tabs, written by the panic hook
+static SNAPSHOT: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SavedSession {
+    /// Index of the tab that was shown
+    #[serde(default)]
+    pub current_tab: usize,
+    #[serde(default, rename = "tab")]
+    pub tabs: Vec<SavedTab>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedTab {
+    pub url: Url,
+    /// Selected row
+    #[serde(default)]
+    pub position: usize,
+    /// Index of the shown page in `pages`
+    #[serde(default)]
+    pub current: usize,
+    /// Pages that can be reached by navigating back and forward
+    #[serde(default, rename = "page")]
+    pub pages: Vec<SavedPage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedPage {
+    pub url: Url,
+    pub title: String,
+    #[serde(default)]
+    pub position: usize,
+}
+
+fn get_session_filename(snapshot: bool) -> PathBuf {
+    let mut dir = dirs::config_dir().expect("no configuration directory");
+    dir.push(env!("CARGO_PKG_NAME"));
+    dir.push(if snapshot {
+        "session-snapshot.toml"
+    } else {
+        "session.toml"
+    });
+    dir
+}
+
+impl SavedSession {
+    /// Loads the last snapshot if ncgopher did not exit cleanly,
+    /// otherwise the session saved on exit. The flag is true for
+    /// snapshots.
+    pub fn load() -> Option<(SavedSession, bool)> {
+        for snapshot in [true, false] {
+            let text = match fs::read_to_string(get_session_filename(snapshot)) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            match toml::from_str::<SavedSession>(&text) {
+                Ok(session) if !session.tabs.is_empty() => return Some((session, snapshot)),
+                Ok(_) => (),
+                Err(err) => warn!("Could not read saved session: {}", err),
+            }
+        }
+        None
+    }
+
+    /// Saves the session when ncgopher exits. The snapshot is not
+    /// needed anymore.
+    pub fn save(&self) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(get_session_filename(false), text)?;
+        *SNAPSHOT.lock().unwrap() = None;
+        match fs::remove_file(get_session_filename(true)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keeps the session as snapshot, it is written to disk by
+    /// `write_snapshot`
+    pub fn set_snapshot(&self) {
+        match toml::to_string(self) {
+            Ok(text) => *SNAPSHOT.lock().unwrap() = Some(text),
+            Err(err) => warn!("Could not serialize session: {}", err),
+        }
+    }
+}
+
+/// Writes the latest snapshot to disk
+pub fn write_snapshot() -> io::Result<()> {
+    // The panic hook must not wait for a snapshot that is being
+    // replaced by the panicking thread
+    let snapshot = match SNAPSHOT.try_lock() {
+        Ok(snapshot) => snapshot.clone(),
+        Err(_) => return Ok(()),
+    };
+    match snapshot {
+        Some(text) => fs::write(get_session_filename(true), text),
+        None => Ok(()),
+    }
+}
+
+/// Removes the saved session and the snapshot
+pub fn remove() {
+    for snapshot in [true, false] {
+        if let Err(err) = fs::remove_file(get_session_filename(snapshot)) {
+            if err.kind() != io::ErrorKind::NotFound {
+                warn!("Could not remove saved session: {}", err);
+            }
+        }
+    }
+}
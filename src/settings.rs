@@ -1,6 +1,8 @@
+use cursive::theme::Style;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs::{self, DirBuilder, File as FsFile};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -14,55 +16,123 @@ pub struct Settings {
     pub config: NewConfig,
     config_filename: String,
     themes: HashMap<String, String>,
+    /// Styles of the `[gemini]` table in the active theme, parsed when
+    /// the theme is selected
+    gemini_styles: HashMap<String, Style>,
 }
 
-fn default_open_new_url() -> char {
-    'g'
+/// Keys bound to an action, either one key sequence or a list of
+/// them. Key sequences are written like "C-f", "M-left" or "g g".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Binding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Binding {
+    pub fn key(sequence: &str) -> Binding {
+        Binding::One(sequence.to_string())
+    }
+
+    pub fn sequences(&self) -> Vec<&str> {
+        match self {
+            Binding::One(sequence) => vec![sequence.as_str()],
+            Binding::Many(sequences) => sequences.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Values that are not key sequences leave the action unbound
+impl Default for Binding {
+    fn default() -> Binding {
+        Binding::Many(Vec::new())
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&self.sequences().join(", "))
+    }
+}
+
+fn default_open_new_url() -> Binding {
+    Binding::key("g")
+}
+fn default_edit_current_url() -> Binding {
+    Binding::key("G")
+}
+fn default_navigate_back() -> Binding {
+    Binding::key("b")
+}
+fn default_navigate_forward() -> Binding {
+    Binding::key("f")
+}
+fn default_close() -> Binding {
+    Binding::key("q")
+}
+fn default_save_page() -> Binding {
+    Binding::key("s")
+}
+fn default_reload_page() -> Binding {
+    Binding::key("r")
+}
+fn default_show_link() -> Binding {
+    Binding::key("i")
+}
+fn default_add_bookmark() -> Binding {
+    Binding::key("a")
 }
-fn default_edit_current_url() -> char {
-    'G'
+fn default_add_to_reading_list() -> Binding {
+    Binding::key("A")
 }
-fn default_navigate_back() -> char {
-    'b'
+fn default_next_link() -> Binding {
+    Binding::key("l")
 }
-fn default_close() -> char {
-    'q'
+fn default_previous_link() -> Binding {
+    Binding::key("L")
 }
-fn default_save_page() -> char {
-    's'
+fn default_move_down() -> Binding {
+    Binding::key("j")
 }
-fn default_reload_page() -> char {
-    'r'
+fn default_move_up() -> Binding {
+    Binding::key("k")
 }
-fn default_show_link() -> char {
-    'i'
+fn default_search_in_text() -> Binding {
+    Binding::key("/")
 }
-fn default_add_bookmark() -> char {
-    'a'
+fn default_next_search_result() -> Binding {
+    Binding::key("n")
 }
-fn default_next_link() -> char {
-    'l'
+fn default_previous_search_result() -> Binding {
+    Binding::key("N")
 }
-fn default_previous_link() -> char {
-    'L'
+fn default_open_in_new_tab() -> Binding {
+    Binding::key("t")
 }
-fn default_move_down() -> char {
-    'j'
+fn default_next_tab() -> Binding {
+    Binding::key("]")
 }
-fn default_move_up() -> char {
-    'k'
+fn default_previous_tab() -> Binding {
+    Binding::key("[")
 }
-fn default_search_in_text() -> char {
-    '/'
+fn default_close_tab() -> Binding {
+    Binding::key("x")
 }
-fn default_next_search_result() -> char {
-    'n'
+fn default_hint_links() -> Binding {
+    Binding::key("F")
 }
-fn default_previous_search_result() -> char {
-    'N'
+fn default_hint_links_new_tab() -> Binding {
+    Binding::key("T")
 }
-fn default_show_help() -> char {
-    '?'
+fn default_hint_links_show_url() -> Binding {
+    Binding::key("I")
+}
+fn default_command_line() -> Binding {
+    Binding::key(":")
+}
+fn default_show_help() -> Binding {
+    Binding::key("?")
 }
 
 pub fn default_keybindings() -> KeyBindings {
@@ -70,11 +140,13 @@ pub fn default_keybindings() -> KeyBindings {
         open_new_url: default_open_new_url(),
         edit_current_url: default_edit_current_url(),
         navigate_back: default_navigate_back(),
+        navigate_forward: default_navigate_forward(),
         close: default_close(),
         save_page: default_save_page(),
         reload_page: default_reload_page(),
         show_link: default_show_link(),
         add_bookmark: default_add_bookmark(),
+        add_to_reading_list: default_add_to_reading_list(),
         next_link: default_next_link(),
         previous_link: default_previous_link(),
         move_up: default_move_up(),
@@ -82,6 +154,14 @@ pub fn default_keybindings() -> KeyBindings {
         search_in_text: default_search_in_text(),
         next_search_result: default_next_search_result(),
         previous_search_result: default_previous_search_result(),
+        open_in_new_tab: default_open_in_new_tab(),
+        next_tab: default_next_tab(),
+        previous_tab: default_previous_tab(),
+        close_tab: default_close_tab(),
+        hint_links: default_hint_links(),
+        hint_links_new_tab: default_hint_links_new_tab(),
+        hint_links_show_url: default_hint_links_show_url(),
+        command_line: default_command_line(),
         show_help: default_show_help(),
     }
 }
@@ -90,46 +170,81 @@ pub fn default_keybindings() -> KeyBindings {
 #[serde(default = "default_keybindings")]
 pub struct KeyBindings {
     #[serde(default = "default_open_new_url", deserialize_with = "ok_or_default")]
-    pub open_new_url: char,
+    pub open_new_url: Binding,
     #[serde(
         default = "default_edit_current_url",
         deserialize_with = "ok_or_default"
     )]
-    pub edit_current_url: char,
+    pub edit_current_url: Binding,
     #[serde(default = "default_navigate_back", deserialize_with = "ok_or_default")]
-    pub navigate_back: char,
+    pub navigate_back: Binding,
+    #[serde(
+        default = "default_navigate_forward",
+        deserialize_with = "ok_or_default"
+    )]
+    pub navigate_forward: Binding,
     #[serde(default = "default_close", deserialize_with = "ok_or_default")]
-    pub close: char,
+    pub close: Binding,
     #[serde(default = "default_save_page", deserialize_with = "ok_or_default")]
-    pub save_page: char,
+    pub save_page: Binding,
     #[serde(default = "default_reload_page", deserialize_with = "ok_or_default")]
-    pub reload_page: char,
+    pub reload_page: Binding,
     #[serde(default = "default_show_link", deserialize_with = "ok_or_default")]
-    pub show_link: char,
+    pub show_link: Binding,
     #[serde(default = "default_add_bookmark", deserialize_with = "ok_or_default")]
-    pub add_bookmark: char,
+    pub add_bookmark: Binding,
+    #[serde(
+        default = "default_add_to_reading_list",
+        deserialize_with = "ok_or_default"
+    )]
+    pub add_to_reading_list: Binding,
     #[serde(default = "default_next_link", deserialize_with = "ok_or_default")]
-    pub next_link: char,
+    pub next_link: Binding,
     #[serde(default = "default_previous_link", deserialize_with = "ok_or_default")]
-    pub previous_link: char,
+    pub previous_link: Binding,
     #[serde(default = "default_move_down", deserialize_with = "ok_or_default")]
-    pub move_down: char,
+    pub move_down: Binding,
     #[serde(default = "default_move_up", deserialize_with = "ok_or_default")]
-    pub move_up: char,
+    pub move_up: Binding,
     #[serde(default = "default_search_in_text", deserialize_with = "ok_or_default")]
-    pub search_in_text: char,
+    pub search_in_text: Binding,
     #[serde(
         default = "default_next_search_result",
         deserialize_with = "ok_or_default"
     )]
-    pub next_search_result: char,
+    pub next_search_result: Binding,
     #[serde(
         default = "default_previous_search_result",
         deserialize_with = "ok_or_default"
     )]
-    pub previous_search_result: char,
+    pub previous_search_result: Binding,
+    #[serde(
+        default = "default_open_in_new_tab",
+        deserialize_with = "ok_or_default"
+    )]
+    pub open_in_new_tab: Binding,
+    #[serde(default = "default_next_tab", deserialize_with = "ok_or_default")]
+    pub next_tab: Binding,
+    #[serde(default = "default_previous_tab", deserialize_with = "ok_or_default")]
+    pub previous_tab: Binding,
+    #[serde(default = "default_close_tab", deserialize_with = "ok_or_default")]
+    pub close_tab: Binding,
+    #[serde(default = "default_hint_links", deserialize_with = "ok_or_default")]
+    pub hint_links: Binding,
+    #[serde(
+        default = "default_hint_links_new_tab",
+        deserialize_with = "ok_or_default"
+    )]
+    pub hint_links_new_tab: Binding,
+    #[serde(
+        default = "default_hint_links_show_url",
+        deserialize_with = "ok_or_default"
+    )]
+    pub hint_links_show_url: Binding,
+    #[serde(default = "default_command_line", deserialize_with = "ok_or_default")]
+    pub command_line: Binding,
     #[serde(default = "default_show_help", deserialize_with = "ok_or_default")]
-    pub show_help: char,
+    pub show_help: Binding,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -160,6 +275,40 @@ pub struct NewConfig {
         deserialize_with = "ok_or_default"
     )]
     pub disable_identities: bool,
+    #[serde(
+        default = "default_keep_raw_downloads",
+        deserialize_with = "ok_or_default"
+    )]
+    pub keep_raw_downloads: bool,
+    #[serde(
+        default = "default_render_ansi_colors",
+        deserialize_with = "ok_or_default"
+    )]
+    pub render_ansi_colors: bool,
+    #[serde(
+        default = "default_syntax_highlighting",
+        deserialize_with = "ok_or_default"
+    )]
+    pub syntax_highlighting: bool,
+    #[serde(default = "default_image_preview", deserialize_with = "ok_or_default")]
+    pub image_preview: bool,
+    #[serde(default = "default_sixel_images", deserialize_with = "ok_or_default")]
+    pub sixel_images: bool,
+    /// Minutes between feed updates, 0 disables updates
+    #[serde(
+        default = "default_feed_update_interval",
+        deserialize_with = "ok_or_default"
+    )]
+    pub feed_update_interval: u32,
+    /// Keep a copy of pages added to the reading list
+    #[serde(
+        default = "default_reading_list_offline",
+        deserialize_with = "ok_or_default"
+    )]
+    pub reading_list_offline: bool,
+    /// Save the open tabs on exit and offer to restore them on startup
+    #[serde(default = "default_save_session", deserialize_with = "ok_or_default")]
+    pub save_session: bool,
 
     // Option<> supports older config files that don't have this.
     pub keybindings: Option<KeyBindings>,
@@ -218,6 +367,30 @@ fn default_disable_history() -> bool {
 fn default_disable_identities() -> bool {
     false
 }
+fn default_keep_raw_downloads() -> bool {
+    false
+}
+fn default_render_ansi_colors() -> bool {
+    true
+}
+fn default_syntax_highlighting() -> bool {
+    true
+}
+fn default_image_preview() -> bool {
+    true
+}
+fn default_sixel_images() -> bool {
+    true
+}
+fn default_feed_update_interval() -> u32 {
+    60
+}
+fn default_reading_list_offline() -> bool {
+    true
+}
+fn default_save_session() -> bool {
+    true
+}
 
 impl Settings {
     pub fn new() -> Settings {
@@ -257,6 +430,27 @@ impl Settings {
             "lightmode".to_string(),
             include_str!("themes/lightmode.toml").to_string(),
         );
+        // Custom themes are read from the themes directory in the
+        // config dir, the file name without extension is the theme name
+        if let Some(mut dir) = dirs::config_dir() {
+            dir.push(env!("CARGO_PKG_NAME"));
+            dir.push("themes");
+            if let Ok(entries) = fs::read_dir(dir) {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+                        continue;
+                    }
+                    let name = path.file_stem().and_then(|name| name.to_str());
+                    match (name, fs::read_to_string(&path)) {
+                        (Some(name), Ok(theme)) => {
+                            themes.insert(name.to_string(), theme);
+                        }
+                        (_, Err(why)) => println!("Could not read theme {:?}: {}", path, why),
+                        _ => (),
+                    }
+                }
+            }
+        }
 
         let mut config_string = String::new();
         if Path::new(confdir.as_str()).exists() {
@@ -264,11 +458,21 @@ impl Settings {
         }
         let config_table: NewConfig = toml::from_str(&config_string).unwrap();
 
-        Settings {
+        let mut settings = Settings {
             config: config_table,
             config_filename,
             themes,
+            gemini_styles: HashMap::new(),
+        };
+        // e.g. a custom theme whose file was deleted
+        let theme = settings.config.theme.clone();
+        if let Err(why) = settings.set_theme(&theme) {
+            println!("{}, using lightmode", why);
+            settings
+                .set_theme("lightmode")
+                .expect("lightmode theme missing");
         }
+        settings
     }
 
     pub fn write_settings_to_file(&mut self) -> std::io::Result<()> {
@@ -284,6 +488,57 @@ impl Settings {
         file.write_all(toml.as_bytes())
     }
 
+    /// Returns the options that can be changed with `set_option` and
+    /// their current values
+    pub fn options(&self) -> Vec<(String, String)> {
+        let table = match Value::try_from(&self.config) {
+            Ok(Value::Table(table)) => table,
+            _ => return Vec::new(),
+        };
+        table
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                Value::String(value) => Some((name, value)),
+                Value::Boolean(value) => Some((name, value.to_string())),
+                Value::Integer(value) => Some((name, value.to_string())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Changes an option of the configuration. The value is parsed
+    /// according to the type of the option.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name == "theme" {
+            return self.set_theme(value);
+        }
+        let mut table = match Value::try_from(&self.config) {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return Err("Invalid configuration".to_string()),
+            Err(err) => return Err(err.to_string()),
+        };
+        let value = match table.get(name) {
+            Some(Value::String(_)) => Value::String(value.to_string()),
+            Some(Value::Boolean(_)) => Value::Boolean(
+                value
+                    .parse()
+                    .map_err(|_| format!("{} must be true or false", name))?,
+            ),
+            Some(Value::Integer(_)) => Value::Integer(
+                value
+                    .parse()
+                    .map_err(|_| format!("{} must be a number", name))?,
+            ),
+            Some(_) => return Err(format!("{} cannot be set", name)),
+            None => return Err(format!("Unknown option {}", name)),
+        };
+        table.insert(name.to_string(), value);
+        self.config = Value::Table(table)
+            .try_into()
+            .map_err(|err: toml::de::Error| err.to_string())?;
+        Ok(())
+    }
+
     /*
     pub fn set<T>(&mut self, key: &str, value: T) -> Result<&mut Config, ConfigError>
     where
@@ -334,7 +589,52 @@ impl Settings {
     }
     */
 
-    pub fn get_theme_by_name(&self, name: String) -> &str {
-        self.themes[&name].as_str()
+    pub fn get_theme_by_name(&self, name: &str) -> Option<&str> {
+        self.themes.get(name).map(String::as_str)
+    }
+
+    /// Returns the active theme
+    pub fn theme(&self) -> &str {
+        self.get_theme_by_name(&self.config.theme)
+            .unwrap_or_else(|| &self.themes["lightmode"])
+    }
+
+    /// Selects a theme and parses its gemini styles
+    pub fn set_theme(&mut self, name: &str) -> Result<(), String> {
+        let theme = self
+            .get_theme_by_name(name)
+            .ok_or_else(|| format!("Unknown theme {}", name))?;
+        self.gemini_styles = parse_gemini_styles(name, theme);
+        self.config.theme = name.to_string();
+        Ok(())
+    }
+
+    /// Returns the styles of the `[gemini]` table in the active theme,
+    /// indexed by the names of gemini::Role
+    pub fn get_gemini_styles(&self) -> HashMap<String, Style> {
+        self.gemini_styles.clone()
+    }
+}
+
+/// Parses the styles of the `[gemini]` table of a theme
+fn parse_gemini_styles(name: &str, theme: &str) -> HashMap<String, Style> {
+    let table = match toml::from_str::<toml::Table>(theme) {
+        Ok(table) => table,
+        Err(why) => {
+            warn!("Could not parse theme {}: {}", name, why);
+            return HashMap::new();
+        }
+    };
+    let mut styles = HashMap::new();
+    if let Some(Value::Table(gemini)) = table.get("gemini") {
+        for (role, style) in gemini {
+            match style.as_str().map(|style| style.parse::<Style>()) {
+                Some(Ok(style)) => {
+                    styles.insert(role.clone(), style);
+                }
+                _ => warn!("Invalid style for {} in theme: {}", role, style),
+            }
+        }
     }
+    styles
 }
@@ -0,0 +1,127 @@
+// Encoding of images as sixel graphics for terminals that support them
+
+use crate::SETTINGS;
+use image::RgbaImage;
+use std::env;
+use std::fmt::Write;
+
+/// Cell size assumed for terminals that don't report their size in pixels
+const DEFAULT_CELL_SIZE: (u32, u32) = (10, 20);
+
+/// Returns true if sixel graphics are enabled in the settings and the
+/// terminal is known to support them
+pub fn supported() -> bool {
+    if !SETTINGS.read().unwrap().config.sixel_images {
+        return false;
+    }
+    // tmux and screen don't pass sixel graphics through reliably
+    if env::var_os("TMUX").is_some() || env::var_os("STY").is_some() {
+        return false;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    term.contains("sixel")
+        || matches!(
+            term.as_str(),
+            "foot" | "foot-extra" | "mlterm" | "yaft-256color" | "contour" | "mintty"
+        )
+        || matches!(program.as_str(), "WezTerm" | "mintty" | "iTerm.app")
+}
+
+/// Returns the size of a terminal cell in pixels
+#[cfg(unix)]
+pub fn cell_size() -> (u32, u32) {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 && size.ws_row > 0 && size.ws_xpixel > 0 && size.ws_ypixel > 0 {
+        (
+            u32::from(size.ws_xpixel / size.ws_col),
+            u32::from(size.ws_ypixel / size.ws_row),
+        )
+    } else {
+        DEFAULT_CELL_SIZE
+    }
+}
+
+#[cfg(not(unix))]
+pub fn cell_size() -> (u32, u32) {
+    DEFAULT_CELL_SIZE
+}
+
+/// Maps a color channel to one of the six levels of the color cube
+fn level(value: u8) -> usize {
+    (usize::from(value) * 5 + 127) / 255
+}
+
+/// Appends `count` repetitions of a sixel character, run-length encoded
+fn push_run(out: &mut String, sixel: char, count: usize) {
+    if count > 3 {
+        write!(out, "!{}{}", count, sixel).unwrap();
+    } else {
+        out.extend(std::iter::repeat_n(sixel, count));
+    }
+}
+
+/// Encodes the image as a sixel sequence. Colors are reduced to a
+/// 6x6x6 color cube, transparent pixels keep the background.
+pub fn encode(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut out = String::new();
+    // The second parameter makes pixels without color transparent
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height).unwrap();
+    for i in 0..216 {
+        write!(
+            out,
+            "#{};2;{};{};{}",
+            i,
+            i / 36 * 20,
+            i / 6 % 6 * 20,
+            i % 6 * 20
+        )
+        .unwrap();
+    }
+    let color = |x, y| {
+        let pixel = image.get_pixel(x, y);
+        if pixel[3] < 128 {
+            None
+        } else {
+            Some(level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2]))
+        }
+    };
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let band: Vec<Option<usize>> = (top..top + rows)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| color(x, y))
+            .collect();
+        let mut used = [false; 216];
+        for i in band.iter().flatten() {
+            used[*i] = true;
+        }
+        for (i, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            write!(out, "#{}", i).unwrap();
+            let mut run = ('?', 0);
+            for x in 0..width as usize {
+                let bits = (0..rows as usize)
+                    .filter(|row| band[row * width as usize + x] == Some(i))
+                    .fold(0, |bits, row| bits | 1 << row);
+                let sixel = char::from(63 + bits as u8);
+                if sixel == run.0 {
+                    run.1 += 1;
+                } else {
+                    push_run(&mut out, run.0, run.1);
+                    run = (sixel, 1);
+                }
+            }
+            // Trailing empty sixels can be left out
+            if run.0 != '?' {
+                push_run(&mut out, run.0, run.1);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
@@ -0,0 +1,73 @@
+use crate::history::Session;
+use crate::readinglist::CachedPage;
+use crate::url_tools::human_readable_url;
+use url::Url;
+
+// Browsing buffers. The controller shows one tab at a time, the state
+// of the other tabs is kept here until they are shown again. Pages
+// opened in a new tab are loaded in the background.
+
+/// Maximum width of a label in the tab bar
+const LABEL_WIDTH: usize = 20;
+
+#[derive(Clone, Debug)]
+pub struct Tab {
+    /// Unique id, used to deliver pages loaded in the background
+    pub id: usize,
+    pub url: Url,
+    pub title: Option<String>,
+    /// Copy of the page, rendered when the tab is shown. Tabs without
+    /// a copy request their page when they are shown.
+    pub page: Option<CachedPage>,
+    /// Selected row
+    pub position: usize,
+    /// Back and forward navigation of the tab
+    pub session: Session,
+    /// Last search, its matches are highlighted again
+    pub search: String,
+    /// True while the page is requested in the background
+    pub loading: bool,
+}
+
+impl Tab {
+    pub fn new(id: usize, url: Url) -> Tab {
+        Tab {
+            id,
+            url,
+            title: None,
+            page: None,
+            position: 0,
+            session: Session::default(),
+            search: String::new(),
+            loading: false,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        label(self.title.as_deref(), &self.url, self.loading)
+    }
+}
+
+/// Returns the title of a page loaded in the background
+pub fn title(page: &CachedPage) -> Option<String> {
+    match page.kind.as_str() {
+        "gophermap" => crate::gophermap::title(&page.content),
+        "gemini" | "markdown" => crate::gemini::title(&page.content),
+        _ => None,
+    }
+}
+
+/// Returns the label of a tab in the tab bar, the title of its page or
+/// its URL if the page has no title
+pub fn label(title: Option<&str>, url: &Url, loading: bool) -> String {
+    let name = title.map_or_else(|| human_readable_url(url), String::from);
+    let mut label: String = name.chars().take(LABEL_WIDTH).collect();
+    if name.chars().count() > LABEL_WIDTH {
+        label.pop();
+        label.push('~');
+    }
+    if loading {
+        label.push_str(" ...");
+    }
+    label
+}
@@ -0,0 +1,303 @@
+use crate::controller::Controller;
+use crate::ui::{dialogs, layout::Layout};
+use crate::url_tools::download_filename_from_url;
+use crate::SETTINGS;
+use cursive::Cursive;
+
+// Commands entered on the command line at the bottom of the screen,
+// e.g. ":open gemini://example.org". The command line shares the edit
+// view with the search.
+
+/// Commands with their subcommands, completed with Tab
+const COMMANDS: [&str; 7] = [
+    "open",
+    "bookmark add",
+    "bookmark rm",
+    "save",
+    "set",
+    "identity use",
+    "history clear",
+];
+
+/// Number of history entries offered as completion of :open
+const HISTORY_COMPLETIONS: usize = 100;
+
+/// Runs a line entered on the command line, without the leading ":"
+pub(crate) fn run(app: &mut Cursive, line: &str) {
+    let (command, args) = split_word(line.trim());
+    match command {
+        "" => (),
+        "open" if args.is_empty() => dialogs::open_url(app),
+        "open" => Controller::open_url_action(app, args),
+        "bookmark" => match split_word(args) {
+            ("add", title) => bookmark_add(app, title),
+            ("rm", name) => bookmark_remove(app, name),
+            _ => message(app, "Usage: bookmark add [TITLE] | bookmark rm [TITLE|URL]"),
+        },
+        "save" => save(app, args),
+        "set" => set(app, args),
+        "identity" => match split_word(args) {
+            ("use", name) if !name.is_empty() => identity_use(app, name),
+            _ => message(app, "Usage: identity use NAME"),
+        },
+        "history" => match split_word(args) {
+            ("clear", age) => history_clear(app, age),
+            _ => message(app, "Usage: history clear [--older-than AGE]"),
+        },
+        other => message(app, &format!("Unknown command: {}", other)),
+    }
+}
+
+/// Completes the command line. Completions are the commands, bookmark
+/// titles, history URLs, identities and options depending on the
+/// command.
+pub(crate) fn complete(app: &mut Cursive) {
+    let content = app
+        .call_on_name("main", |v: &mut Layout| v.search.get_content())
+        .expect("main layout missing");
+    let line = content.strip_prefix(':').unwrap_or(&content);
+    let completions: Vec<String> = completions(app, line)
+        .into_iter()
+        .map(|c| format!(":{}", c))
+        .collect();
+    match completions.len() {
+        0 => message(app, "No completions"),
+        1 => (),
+        n => message(
+            app,
+            &format!("{} completions, press Tab for the next one", n),
+        ),
+    }
+    app.call_on_name("main", |v: &mut Layout| v.set_completions(completions))
+        .expect("main layout missing");
+}
+
+fn completions(app: &mut Cursive, line: &str) -> Vec<String> {
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .filter(|c| c.starts_with(line))
+        .map(|c| format!("{} ", c))
+        .collect();
+    if !commands.is_empty() {
+        return commands;
+    }
+    let command = match COMMANDS
+        .iter()
+        .find(|c| line.starts_with(&format!("{} ", c)))
+    {
+        Some(command) => *command,
+        None => return Vec::new(),
+    };
+    let arg = line[command.len()..].trim_start().to_lowercase();
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let mut values: Vec<String> = match command {
+        "open" => {
+            let mut urls: Vec<String> = controller
+                .bookmarks
+                .lock()
+                .unwrap()
+                .get_bookmarks()
+                .into_iter()
+                .map(|b| b.url.to_string())
+                .collect();
+            let history = controller
+                .history
+                .lock()
+                .unwrap()
+                .get_latest_history(HISTORY_COMPLETIONS)
+                .unwrap_or_default();
+            urls.extend(history.into_iter().map(|e| e.url.to_string()));
+            urls
+        }
+        "bookmark rm" => controller
+            .bookmarks
+            .lock()
+            .unwrap()
+            .get_bookmarks()
+            .into_iter()
+            .map(|b| b.title)
+            .collect(),
+        "set" => SETTINGS
+            .read()
+            .unwrap()
+            .options()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect(),
+        "identity use" => controller
+            .client_certificates
+            .lock()
+            .unwrap()
+            .get_client_certificates()
+            .into_iter()
+            .map(|cc| cc.common_name)
+            .collect(),
+        "history clear" => vec!["--older-than 30d".to_string()],
+        _ => Vec::new(),
+    };
+    values.retain(|v| v.to_lowercase().contains(&arg));
+    values.sort();
+    values.dedup();
+    values
+        .into_iter()
+        .map(|v| format!("{} {}", command, v))
+        .collect()
+}
+
+/// Splits the first word off a line
+fn split_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+fn message(app: &mut Cursive, msg: &str) {
+    app.user_data::<Controller>()
+        .expect("controller missing")
+        .set_message(msg);
+}
+
+/// Bookmarks the current page, with the page title if no title is given
+fn bookmark_add(app: &mut Cursive, title: &str) {
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let url = controller.current_url.lock().unwrap().clone();
+    let title = match title {
+        "" => controller
+            .current_title
+            .clone()
+            .unwrap_or_else(|| url.to_string()),
+        title => title.to_string(),
+    };
+    controller.set_message(&format!("Bookmarked {}", url));
+    controller.add_bookmark_action(url, title, String::new(), String::new());
+}
+
+/// Removes the bookmark with the title or URL, or the bookmark of the
+/// current page
+fn bookmark_remove(app: &mut Cursive, name: &str) {
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let current_url = controller.current_url.lock().unwrap().to_string();
+    let name = if name.is_empty() { &current_url } else { name };
+    let bookmark = controller
+        .bookmarks
+        .lock()
+        .unwrap()
+        .get_bookmarks()
+        .into_iter()
+        .find(|b| b.title == name || b.url.as_str() == name);
+    match bookmark {
+        Some(bookmark) => {
+            controller.set_message(&format!("Removed bookmark {}", bookmark.title));
+            Controller::remove_bookmark_action(app, bookmark);
+        }
+        None => controller.set_message(&format!("No bookmark {}", name)),
+    }
+}
+
+/// Saves the current page, by default under the name the save dialog
+/// suggests
+fn save(app: &mut Cursive, path: &str) {
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let path = match path {
+        "" => download_filename_from_url(&controller.current_url.lock().unwrap()),
+        path => path.to_string(),
+    };
+    controller.save_page(path);
+}
+
+/// Shows an option with "set NAME" or changes it with "set NAME=VALUE"
+fn set(app: &mut Cursive, args: &str) {
+    let (name, value) = match args.split_once('=') {
+        Some((name, value)) => (name.trim(), value.trim()),
+        None => {
+            let value = SETTINGS
+                .read()
+                .unwrap()
+                .options()
+                .into_iter()
+                .find(|(name, _)| name == args);
+            match value {
+                Some((name, value)) => message(app, &format!("{}={}", name, value)),
+                None => message(app, &format!("Unknown option {}", args)),
+            }
+            return;
+        }
+    };
+    let result = SETTINGS.write().unwrap().set_option(name, value);
+    if let Err(err) = result {
+        message(app, &err);
+        return;
+    }
+    if name == "theme" {
+        let theme = SETTINGS.read().unwrap().theme().to_string();
+        if let Err(err) = app.load_toml(&theme) {
+            warn!("Could not load theme {}: {:?}", value, err);
+        }
+    }
+    if let Err(err) = SETTINGS.write().unwrap().write_settings_to_file() {
+        message(app, &format!("Could not write config file: {}", err));
+        return;
+    }
+    let index = Controller::get_selected_item_index(app);
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    controller.set_message(&format!("{}={}", name, value));
+    controller.redraw_page(index);
+}
+
+/// Uses the identity with the common name for the current gemini site
+/// and reloads the page
+fn identity_use(app: &mut Cursive, name: &str) {
+    let cc = app
+        .user_data::<Controller>()
+        .expect("controller missing")
+        .client_certificates
+        .lock()
+        .unwrap()
+        .get_client_certificates()
+        .into_iter()
+        .find(|cc| cc.common_name == name);
+    let cc = match cc {
+        Some(cc) => cc,
+        None => return message(app, &format!("No identity {}", name)),
+    };
+    if !Controller::use_current_site_client_certificate_action(app, cc) {
+        return message(app, "Identities can only be used on gemini sites");
+    }
+    let index = Controller::get_selected_item_index(app);
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let current_url = controller.current_url.lock().unwrap().clone();
+    controller.open_url(current_url, false, index);
+}
+
+/// Clears the history, or only the entries older than an age like
+/// "30d", "2w" or "12h"
+fn history_clear(app: &mut Cursive, args: &str) {
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    let age = match split_word(args) {
+        ("", _) => {
+            controller.clear_history();
+            controller.set_message("History cleared");
+            return;
+        }
+        ("--older-than", age) => age,
+        _ => return controller.set_message("Usage: history clear [--older-than AGE]"),
+    };
+    match parse_age(age) {
+        Some(hours) => controller.clear_history_older_than(hours),
+        None => controller.set_message(&format!("Invalid age {}, use e.g. 30d", age)),
+    }
+}
+
+/// Parses an age in hours, days or weeks into hours
+fn parse_age(age: &str) -> Option<u32> {
+    let (number, unit) = age.split_at(age.find(|c: char| !c.is_ascii_digit())?);
+    let number: u32 = number.parse().ok()?;
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        _ => return None,
+    };
+    number.checked_mul(hours)
+}
@@ -1,16 +1,23 @@
-use crate::bookmarks::Bookmark;
+use crate::bookmarks::{Bookmark, ExportFormat};
 use crate::clientcertificates::ClientCertificate;
+use crate::feeds::{SourceKind, Subscription};
 use crate::history::HistoryEntry;
+use crate::readinglist::ReadingListItem;
+use crate::session::SavedSession;
+use crate::ui::imageview::ImageView;
 use crate::url_tools::download_filename_from_url;
 use crate::{Controller, SETTINGS};
 use cursive::{
+    event::Key,
     view::{Nameable, Resizable, Scrollable},
     views::{
-        Button, Checkbox, Dialog, DummyView, EditView, LinearLayout, RadioButton, RadioGroup,
-        SelectView, TextArea, TextView,
+        Button, Checkbox, Dialog, DummyView, EditView, LinearLayout, OnEventView, RadioButton,
+        RadioGroup, SelectView, TextArea, TextView,
     },
     Cursive,
 };
+use image::DynamicImage;
+use std::path::PathBuf;
 use std::time::SystemTime;
 use std::vec::Vec;
 use time::{format_description, Date, OffsetDateTime};
@@ -19,14 +26,11 @@ use url::{Position, Url};
 pub(super) fn add_bookmark_current_url(app: &mut Cursive) {
     let controller = app.user_data::<Controller>().expect("controller missing");
     let current_url = controller.current_url.lock().unwrap().clone();
-    add_bookmark(app, current_url);
+    let title = controller.current_title.clone().unwrap_or_default();
+    edit_bookmark(app, current_url, &title, "", "");
 }
 
-pub(crate) fn add_bookmark(app: &mut Cursive, url: Url) {
-    edit_bookmark(app, url, "", "");
-}
-
-pub fn edit_bookmark(app: &mut Cursive, url: Url, title: &str, tags: &str) {
+pub fn edit_bookmark(app: &mut Cursive, url: Url, title: &str, tags: &str, folder: &str) {
     app.add_layer(
         Dialog::new()
             .title("Add Bookmark")
@@ -52,12 +56,20 @@ pub fn edit_bookmark(app: &mut Cursive, url: Url, title: &str, tags: &str) {
                             .content(tags)
                             .with_name("tags")
                             .fixed_width(30),
+                    )
+                    .child(TextView::new("Folder (like Tech/Gemini):"))
+                    .child(
+                        EditView::new()
+                            .content(folder)
+                            .with_name("folder")
+                            .fixed_width(30),
                     ),
             )
             .button("Ok", |app| {
                 let url = app.find_name::<EditView>("url").unwrap().get_content();
                 let title = app.find_name::<EditView>("title").unwrap().get_content();
                 let tags = app.find_name::<EditView>("tags").unwrap().get_content();
+                let folder = app.find_name::<EditView>("folder").unwrap().get_content();
 
                 // Validate URL
                 if let Ok(url) = Url::parse(&url) {
@@ -65,7 +77,12 @@ pub fn edit_bookmark(app: &mut Cursive, url: Url, title: &str, tags: &str) {
                     app.pop_layer();
                     app.user_data::<Controller>()
                         .expect("controller missing")
-                        .add_bookmark_action(url, (*title).clone(), (*tags).clone());
+                        .add_bookmark_action(
+                            url,
+                            (*title).clone(),
+                            (*tags).clone(),
+                            (*folder).clone(),
+                        );
                 } else {
                     // do not close the dialog so the user can make
                     // corrections
@@ -96,7 +113,30 @@ pub(crate) fn certificate_changed(app: &mut Cursive, url: Url, fingerprint: Stri
     );
 }
 
-pub(super) fn edit_bookmarks(app: &mut Cursive) {
+fn bookmark_label(b: &Bookmark) -> String {
+    let title = match b.folder.as_str() {
+        "" => b.title.clone(),
+        folder => format!("{}/{}", folder, b.title),
+    };
+    let mut title = format!("{:<25}", title);
+    title.truncate(25);
+    let mut url = format!("{:<40}", b.url.as_str());
+    url.truncate(40);
+    let mut tags = b.tags.join(",");
+    tags.truncate(20);
+    format!("{} | {} | {}", title, url, tags)
+}
+
+/// Shows the bookmarks with the selected tag that match the search
+fn filter_bookmarks(app: &mut Cursive) {
+    let tag = app
+        .find_name::<SelectView<Option<String>>>("bookmark_tag")
+        .and_then(|view| view.selection())
+        .and_then(|tag| (*tag).clone());
+    let search = app
+        .find_name::<EditView>("bookmark_search")
+        .map(|view| view.get_content())
+        .unwrap_or_default();
     let bookmarks = app
         .user_data::<Controller>()
         .expect("controller missing")
@@ -104,18 +144,74 @@ pub(super) fn edit_bookmarks(app: &mut Cursive) {
         .lock()
         .unwrap()
         .get_bookmarks();
-    let mut view: SelectView<Bookmark> = SelectView::new();
-    for b in bookmarks {
-        let mut title = format!("{:<20}", b.title.clone().as_str());
-        title.truncate(20);
-        let mut url = format!("{:<50}", b.url.clone().as_str());
-        url.truncate(50);
-        view.add_item(format!("{} | {}", title, url), b);
+    app.call_on_name("bookmarks", |view: &mut SelectView<Bookmark>| {
+        view.clear();
+        for b in bookmarks {
+            let has_tag = tag.as_ref().is_none_or(|tag| b.has_tag(tag));
+            if has_tag && b.matches(&search) {
+                view.add_item(bookmark_label(&b), b);
+            }
+        }
+    });
+}
+
+/// Moves the selected bookmark up or down, past the neighbouring
+/// bookmark shown in the list
+fn move_bookmark(app: &mut Cursive, up: bool) {
+    let swapped = app
+        .call_on_name("bookmarks", |view: &mut SelectView<Bookmark>| {
+            let i = view.selected_id()?;
+            let j = if up { i.checked_sub(1)? } else { i + 1 };
+            let neighbour = view.get_item(j)?.1.url.clone();
+            let (label, b) = view.get_item(i).map(|(l, b)| (l.to_string(), b.clone()))?;
+            let url = b.url.clone();
+            view.remove_item(i);
+            view.insert_item(j, label, b);
+            view.set_selection(j);
+            Some((url, neighbour))
+        })
+        .flatten();
+    if let Some((a, b)) = swapped {
+        Controller::swap_bookmarks_action(app, &a, &b);
+    }
+}
+
+pub(super) fn edit_bookmarks(app: &mut Cursive) {
+    let tags = app
+        .user_data::<Controller>()
+        .expect("controller missing")
+        .bookmarks
+        .lock()
+        .unwrap()
+        .tags();
+    let mut tag_view: SelectView<Option<String>> = SelectView::new().popup();
+    tag_view.add_item("All tags", None);
+    for tag in tags {
+        tag_view.add_item(tag.clone(), Some(tag));
     }
+    tag_view.set_on_submit(|app, _| filter_bookmarks(app));
+    let view: SelectView<Bookmark> = SelectView::new();
     app.add_layer(
         Dialog::new()
             .title("Edit bookmarks")
-            .content(LinearLayout::vertical().child(view.with_name("bookmarks").scrollable()))
+            .content(
+                LinearLayout::vertical()
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(TextView::new("Tag: "))
+                            .child(tag_view.with_name("bookmark_tag"))
+                            .child(DummyView)
+                            .child(TextView::new("Search: "))
+                            .child(
+                                EditView::new()
+                                    .on_edit(|app, _, _| filter_bookmarks(app))
+                                    .with_name("bookmark_search")
+                                    .fixed_width(30),
+                            ),
+                    )
+                    .child(DummyView)
+                    .child(view.with_name("bookmarks").scrollable()),
+            )
             .button("Delete", |app| {
                 let selected = app
                     .call_on_name("bookmarks", |view: &mut SelectView<Bookmark>| {
@@ -164,16 +260,385 @@ pub(super) fn edit_bookmarks(app: &mut Cursive) {
                             b.url.clone(),
                             &b.title,
                             &b.tags.join(","),
+                            &b.folder,
                         );
                     }
                 }
             })
+            .button("Move up", |app| move_bookmark(app, true))
+            .button("Move down", |app| move_bookmark(app, false))
+            .button("Close", |app| {
+                app.pop_layer();
+            }),
+    );
+    filter_bookmarks(app);
+}
+
+pub(super) fn export_bookmarks(app: &mut Cursive) {
+    let mut formats: SelectView<ExportFormat> = SelectView::new().popup();
+    for format in ExportFormat::ALL.iter() {
+        formats.add_item(format.name(), *format);
+    }
+    // suggest a file name matching the format
+    formats.set_on_submit(|app, format: &ExportFormat| {
+        app.call_on_name("export_filename", |view: &mut EditView| {
+            view.set_content(format.filename());
+        });
+    });
+    app.add_layer(
+        Dialog::new()
+            .title("Export bookmarks")
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Format:"))
+                    .child(formats.with_name("export_format"))
+                    .child(TextView::new("\nFilename:"))
+                    .child(
+                        EditView::new()
+                            .content(ExportFormat::ALL[0].filename())
+                            .with_name("export_filename")
+                            .fixed_width(50),
+                    ),
+            )
+            .button("Cancel", |app| {
+                app.pop_layer();
+            })
+            .button("Export", |app| {
+                let format = app
+                    .find_name::<SelectView<ExportFormat>>("export_format")
+                    .unwrap()
+                    .selection()
+                    .map_or(ExportFormat::ALL[0], |format| *format);
+                let path = app
+                    .find_name::<EditView>("export_filename")
+                    .unwrap()
+                    .get_content();
+                Controller::export_bookmarks_action(app, format, &path);
+            }),
+    );
+}
+
+pub(super) fn import_bookmarks(app: &mut Cursive) {
+    app.add_layer(
+        Dialog::new()
+            .title("Import bookmarks")
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "Bookmarks of Lagrange (bookmarks.ini), Amfora (bookmarks.xml,\n\
+                         bookmarks.toml), Bombadillo (.bombadillo.ini) or a gemtext\n\
+                         link list. Bookmarks already known are merged.\n\nFilename:",
+                    ))
+                    .child(
+                        EditView::new()
+                            .on_submit(Controller::import_bookmarks_action)
+                            .with_name("import_filename")
+                            .fixed_width(50),
+                    ),
+            )
+            .button("Cancel", |app| {
+                app.pop_layer();
+            })
+            .button("Import", |app| {
+                let path = app
+                    .find_name::<EditView>("import_filename")
+                    .unwrap()
+                    .get_content();
+                Controller::import_bookmarks_action(app, &path);
+            }),
+    );
+}
+
+/// Lists the feed subscriptions or the watched gophermaps
+pub(super) fn manage_subscriptions(app: &mut Cursive, kind: SourceKind) {
+    let subscriptions = app
+        .user_data::<Controller>()
+        .expect("controller missing")
+        .feeds
+        .lock()
+        .unwrap()
+        .subscriptions()
+        .expect("could not get subscriptions");
+    let mut view: SelectView<Subscription> = SelectView::new();
+    for s in subscriptions.into_iter().filter(|s| s.kind == kind) {
+        let mut title = format!("{:<30}", s.title.as_str());
+        title.truncate(30);
+        let mut url = format!("{:<50}", s.url.as_str());
+        url.truncate(50);
+        view.add_item(format!("{} | {}", title, url), s);
+    }
+    let (title, remove) = match kind {
+        SourceKind::Feed => ("Manage subscriptions", "Unsubscribe"),
+        SourceKind::Phlog => ("Manage watched phlogs", "Stop watching"),
+    };
+    app.add_layer(
+        Dialog::new()
+            .title(title)
+            .content(LinearLayout::vertical().child(view.with_name("subscriptions").scrollable()))
+            .button(remove, move |app| {
+                let selected = app
+                    .find_name::<SelectView<Subscription>>("subscriptions")
+                    .expect("subscriptions view missing")
+                    .selection();
+                if let Some(subscription) = selected {
+                    app.call_on_name("subscriptions", |view: &mut SelectView<Subscription>| {
+                        view.remove_item(view.selected_id().unwrap());
+                    })
+                    .unwrap();
+                    let controller = app.user_data::<Controller>().expect("controller missing");
+                    let result = controller
+                        .feeds
+                        .lock()
+                        .unwrap()
+                        .unsubscribe(subscription.id);
+                    match (result, kind) {
+                        (Ok(()), SourceKind::Feed) => controller
+                            .set_message(&format!("Unsubscribed from {}", subscription.title)),
+                        (Ok(()), SourceKind::Phlog) => controller
+                            .set_message(&format!("Stopped watching {}", subscription.title)),
+                        (Err(err), _) => {
+                            controller.set_message(&format!("Could not unsubscribe: {}", err))
+                        }
+                    }
+                }
+            })
+            .button("Open", |app| {
+                let selected = app
+                    .find_name::<SelectView<Subscription>>("subscriptions")
+                    .expect("subscriptions view missing")
+                    .selection();
+                app.pop_layer();
+                if let Some(s) = selected {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .open_url(s.url.clone(), true, 0);
+                }
+            })
             .button("Close", |app| {
                 app.pop_layer();
             }),
     );
 }
 
+fn reading_list_label(item: &ReadingListItem) -> String {
+    let mut title = format!("{:<30}", item.title.as_str());
+    title.truncate(30);
+    let mut url = format!("{:<40}", item.url.as_str());
+    url.truncate(40);
+    format!(
+        "{} {}{} | {}",
+        if item.read { "[x]" } else { "[ ]" },
+        title,
+        if item.cached { "*" } else { " " },
+        url
+    )
+}
+
+pub(super) fn reading_list(app: &mut Cursive) {
+    let items = app
+        .user_data::<Controller>()
+        .expect("controller missing")
+        .reading_list
+        .lock()
+        .unwrap()
+        .items()
+        .expect("could not get reading list");
+    let mut view: SelectView<ReadingListItem> = SelectView::new();
+    for item in items {
+        view.add_item(reading_list_label(&item), item);
+    }
+    app.add_layer(
+        Dialog::new()
+            .title("Reading list")
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Read / Title (* = offline copy) / URL"))
+                    .child(
+                        LinearLayout::vertical().child(view.with_name("reading_list").scrollable()),
+                    ),
+            )
+            .button("Open", |app| {
+                let selected = app
+                    .find_name::<SelectView<ReadingListItem>>("reading_list")
+                    .expect("reading list view missing")
+                    .selection();
+                app.pop_layer();
+                if let Some(item) = selected {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .open_reading_list_item(&item, false);
+                }
+            })
+            .button("Read offline", |app| {
+                let selected = app
+                    .find_name::<SelectView<ReadingListItem>>("reading_list")
+                    .expect("reading list view missing")
+                    .selection();
+                app.pop_layer();
+                if let Some(item) = selected {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .open_reading_list_item(&item, true);
+                }
+            })
+            .button("Toggle read", |app| {
+                let selected = app
+                    .find_name::<SelectView<ReadingListItem>>("reading_list")
+                    .expect("reading list view missing")
+                    .selection();
+                if let Some(item) = selected {
+                    let mut item = (*item).clone();
+                    item.read = !item.read;
+                    let controller = app.user_data::<Controller>().expect("controller missing");
+                    let result = controller
+                        .reading_list
+                        .lock()
+                        .unwrap()
+                        .set_read(item.id, item.read);
+                    if let Err(err) = result {
+                        controller.set_message(&format!("Could not update reading list: {}", err));
+                        return;
+                    }
+                    app.call_on_name("reading_list", |view: &mut SelectView<ReadingListItem>| {
+                        let id = view.selected_id().unwrap();
+                        view.remove_item(id);
+                        view.insert_item(id, reading_list_label(&item), item);
+                        view.set_selection(id);
+                    })
+                    .unwrap();
+                }
+            })
+            .button("Remove", |app| {
+                let selected = app
+                    .find_name::<SelectView<ReadingListItem>>("reading_list")
+                    .expect("reading list view missing")
+                    .selection();
+                if let Some(item) = selected {
+                    app.call_on_name("reading_list", |view: &mut SelectView<ReadingListItem>| {
+                        view.remove_item(view.selected_id().unwrap());
+                    })
+                    .unwrap();
+                    let controller = app.user_data::<Controller>().expect("controller missing");
+                    let result = controller.reading_list.lock().unwrap().remove(item.id);
+                    match result {
+                        Ok(()) => controller
+                            .set_message(&format!("Removed {} from the reading list", item.title)),
+                        Err(err) => controller.set_message(&format!(
+                            "Could not remove from the reading list: {}",
+                            err
+                        )),
+                    }
+                }
+            })
+            .button("Close", |app| {
+                app.pop_layer();
+            }),
+    );
+}
+
+/// Offers to restore the tabs of the last session. Called on startup.
+pub(crate) fn restore_session(app: &mut Cursive) {
+    if !SETTINGS.read().unwrap().config.save_session {
+        return;
+    }
+    let (saved, snapshot) = match SavedSession::load() {
+        Some(loaded) => loaded,
+        None => return,
+    };
+    let tabs = match saved.tabs.len() {
+        1 => "the tab".to_string(),
+        n => format!("the {} tabs", n),
+    };
+    let text = if snapshot {
+        format!(
+            "ncgopher was not closed properly.\nDo you want to restore {} that were open?",
+            tabs
+        )
+    } else {
+        format!("Do you want to restore {} of your last session?", tabs)
+    };
+    app.add_layer(
+        Dialog::around(TextView::new(text))
+            .title("Restore session")
+            .button("New session", |app| {
+                app.pop_layer();
+                crate::session::remove();
+            })
+            .button("Restore", move |app| {
+                app.pop_layer();
+                crate::session::remove();
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .restore_session(saved.clone());
+            }),
+    );
+}
+
+/// Shows all pages of the current session as a tree and navigates to
+/// the selected one
+pub(super) fn session_tree(app: &mut Cursive) {
+    let items = app
+        .user_data::<Controller>()
+        .expect("controller missing")
+        .history
+        .lock()
+        .unwrap()
+        .session
+        .tree();
+    if items.is_empty() {
+        app.add_layer(Dialog::info("No pages visited in this session."));
+        return;
+    }
+    let mut view: SelectView<usize> = SelectView::new();
+    let mut selected = 0;
+    for (row, item) in items.iter().enumerate() {
+        if item.current {
+            selected = row;
+        }
+        let mut title = item.entry.title.clone();
+        title.truncate(60);
+        view.add_item(
+            format!(
+                "{}{} {}",
+                "  ".repeat(item.depth),
+                if item.current { ">" } else { "*" },
+                title
+            ),
+            item.id,
+        );
+    }
+    view.set_selection(selected);
+    view.set_on_submit(|app, id| {
+        app.pop_layer();
+        navigate_to(app, *id);
+    });
+    app.add_layer(
+        Dialog::new()
+            .title("Session tree")
+            .content(view.with_name("session_tree").scrollable())
+            .button("Open", |app| {
+                let selected = app
+                    .find_name::<SelectView<usize>>("session_tree")
+                    .expect("session tree view missing")
+                    .selection();
+                app.pop_layer();
+                if let Some(id) = selected {
+                    navigate_to(app, *id);
+                }
+            })
+            .button("Close", |app| {
+                app.pop_layer();
+            }),
+    );
+}
+
+fn navigate_to(app: &mut Cursive, id: usize) {
+    let index = Controller::get_selected_item_index(app);
+    app.user_data::<Controller>()
+        .expect("controller missing")
+        .navigate_to(index, id);
+}
+
 pub(super) fn edit_history(app: &mut Cursive) {
     let entries = app
         .user_data::<Controller>()
@@ -191,11 +656,18 @@ pub(super) fn edit_history(app: &mut Cursive) {
     for e in entries {
         let mut url = e.url.to_string();
         url.truncate(50);
+        // the title falls back to the URL for pages without a title
+        let title = if e.title == e.url.as_str() {
+            String::new()
+        } else {
+            e.title.chars().take(30).collect()
+        };
         view.add_item(
             format!(
-                "{:>4}|{:<20}|{}",
+                "{:>4}|{:<20}|{:<30}|{}",
                 e.visited_count,
                 e.timestamp.format(&format).expect("Invalid timestamp from database"),
+                title,
                 url
             ),
             e,
@@ -206,7 +678,9 @@ pub(super) fn edit_history(app: &mut Cursive) {
             .title("Show history")
             .content(
                 LinearLayout::vertical()
-                    .child(TextView::new("#Vis|Last Visited        |URL"))
+                    .child(TextView::new(
+                        "#Vis|Last Visited        |Title                         |URL",
+                    ))
                     .child(LinearLayout::vertical().child(view.with_name("entries").scrollable())),
             )
             .button("Clear all history", |app| {
@@ -279,6 +753,92 @@ pub(crate) fn gemini_query(app: &mut Cursive, url: Url, query: String, secret: b
     );
 }
 
+/// Dialog for querying a CSO phone-book server (gopher item type 2)
+pub(crate) fn cso_query(app: &mut Cursive, url: Url) {
+    let query_url = url.clone();
+    let fields_url = url.clone();
+    app.add_layer(
+        Dialog::new()
+            .title(format!(
+                "CSO phone book: {}",
+                url.host_str().unwrap_or_default()
+            ))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Query (e.g. \"smith\" or \"name=smith\"):"))
+                    .child(
+                        EditView::new()
+                            .on_submit(move |app, query| {
+                                if !query.is_empty() {
+                                    app.user_data::<Controller>()
+                                        .expect("controller missing")
+                                        .query_cso(url.clone(), format!("query {}", query));
+                                }
+                            })
+                            .with_name("cso_query")
+                            .fixed_width(60),
+                    )
+                    .child(DummyView)
+                    .child(
+                        TextView::new("")
+                            .with_name("cso_result")
+                            .scrollable()
+                            .fixed_size((60, 15)),
+                    ),
+            )
+            .button("Query", move |app| {
+                let query = app
+                    .find_name::<EditView>("cso_query")
+                    .expect("query field missing")
+                    .get_content();
+                if !query.is_empty() {
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .query_cso(query_url.clone(), format!("query {}", query));
+                }
+            })
+            .button("Fields", move |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .query_cso(fields_url.clone(), "fields".to_string());
+            })
+            .button("Close", |app| {
+                app.pop_layer();
+            }),
+    );
+}
+
+/// Shows a preview of an image in a popup sized to the screen. The
+/// image can still be opened in the external images viewer, either
+/// from its URL or from the downloaded file.
+pub(crate) fn image_preview(
+    app: &mut Cursive,
+    url: Url,
+    image: DynamicImage,
+    path: Option<PathBuf>,
+) {
+    let view = ImageView::new(image);
+    let close = |app: &mut Cursive| {
+        app.pop_layer();
+        // Remove any sixel graphics left on the screen
+        app.clear();
+    };
+    app.add_layer(
+        OnEventView::new(
+            Dialog::around(view)
+                .title(url.as_str())
+                .button("Open in viewer", move |app| {
+                    close(app);
+                    app.user_data::<Controller>()
+                        .expect("controller missing")
+                        .open_image_externally(url.clone(), path.clone());
+                })
+                .button("Close", close),
+        )
+        .on_event(Key::Esc, close),
+    );
+}
+
 pub(super) fn open_url(app: &mut Cursive) {
     open_given_url(app, None);
 }
@@ -363,8 +923,16 @@ pub(super) fn settings(app: &mut Cursive) {
     let telnet_command = SETTINGS.read().unwrap().config.telnet_command.clone();
     let darkmode = theme == "darkmode";
     let textwrap = SETTINGS.read().unwrap().config.textwrap.clone();
+    let feed_update_interval = SETTINGS.read().unwrap().config.feed_update_interval;
     let disable_history = SETTINGS.read().unwrap().config.disable_history;
     let disable_identities = SETTINGS.read().unwrap().config.disable_identities;
+    let keep_raw_downloads = SETTINGS.read().unwrap().config.keep_raw_downloads;
+    let render_ansi_colors = SETTINGS.read().unwrap().config.render_ansi_colors;
+    let syntax_highlighting = SETTINGS.read().unwrap().config.syntax_highlighting;
+    let image_preview = SETTINGS.read().unwrap().config.image_preview;
+    let sixel_images = SETTINGS.read().unwrap().config.sixel_images;
+    let reading_list_offline = SETTINGS.read().unwrap().config.reading_list_offline;
+    let save_session = SETTINGS.read().unwrap().config.save_session;
     app.add_layer(
         Dialog::new()
             .title("Settings")
@@ -397,12 +965,52 @@ pub(super) fn settings(app: &mut Cursive) {
                            .child(DummyView)
                            .child(TextView::new("Disable identities"))
                     )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(keep_raw_downloads).with_name("keep_raw_downloads"))
+                           .child(DummyView)
+                           .child(TextView::new("Keep raw uuencoded/BinHex downloads"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(render_ansi_colors).with_name("render_ansi_colors"))
+                           .child(DummyView)
+                           .child(TextView::new("Render ANSI colors (otherwise strip them)"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(syntax_highlighting).with_name("syntax_highlighting"))
+                           .child(DummyView)
+                           .child(TextView::new("Syntax highlighting in preformatted blocks"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(image_preview).with_name("image_preview"))
+                           .child(DummyView)
+                           .child(TextView::new("Preview images (otherwise use the images viewer)"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(sixel_images).with_name("sixel_images"))
+                           .child(DummyView)
+                           .child(TextView::new("Sixel graphics if supported by the terminal"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(reading_list_offline).with_name("reading_list_offline"))
+                           .child(DummyView)
+                           .child(TextView::new("Keep reading list pages for offline reading"))
+                    )
+                    .child(LinearLayout::horizontal()
+                           .child(Checkbox::new().with_checked(save_session).with_name("save_session"))
+                           .child(DummyView)
+                           .child(TextView::new("Save open tabs and restore them on startup"))
+                    )
                     .child(DummyView)
                     .child(LinearLayout::horizontal()
                            .child(TextView::new("Text wrap column:"))
                            .child(DummyView)
                            .child(EditView::new().content(textwrap.as_str()).with_name("textwrap").fixed_width(5))
                     )
+                    .child(LinearLayout::horizontal()
+                           .child(TextView::new("Feed update interval (minutes, 0 = never):"))
+                           .child(DummyView)
+                           .child(EditView::new().content(feed_update_interval.to_string()).with_name("feed_update_interval").fixed_width(5))
+                    )
             )
             .button("Apply",  |app| {
                 let homepage = app.find_name::<EditView>("homepage").unwrap().get_content();
@@ -410,10 +1018,18 @@ pub(super) fn settings(app: &mut Cursive) {
                 let darkmode = app.find_name::<Checkbox>("darkmode").unwrap().is_checked();
                 let disable_history = app.find_name::<Checkbox>("disable_history").unwrap().is_checked();
                 let disable_identities = app.find_name::<Checkbox>("disable_identities").unwrap().is_checked();
+                let keep_raw_downloads = app.find_name::<Checkbox>("keep_raw_downloads").unwrap().is_checked();
+                let render_ansi_colors = app.find_name::<Checkbox>("render_ansi_colors").unwrap().is_checked();
+                let syntax_highlighting = app.find_name::<Checkbox>("syntax_highlighting").unwrap().is_checked();
+                let image_preview = app.find_name::<Checkbox>("image_preview").unwrap().is_checked();
+                let sixel_images = app.find_name::<Checkbox>("sixel_images").unwrap().is_checked();
+                let reading_list_offline = app.find_name::<Checkbox>("reading_list_offline").unwrap().is_checked();
+                let save_session = app.find_name::<Checkbox>("save_session").unwrap().is_checked();
                 let html_command = app.find_name::<EditView>("html_command").unwrap().get_content();
                 let image_command = app.find_name::<EditView>("image_command").unwrap().get_content();
                 let telnet_command = app.find_name::<EditView>("telnet_command").unwrap().get_content();
                 let textwrap = app.find_name::<EditView>("textwrap").unwrap().get_content();
+                let feed_update_interval = app.find_name::<EditView>("feed_update_interval").unwrap().get_content();
                 app.pop_layer();
                 if Url::parse(&homepage).is_ok() {
                     // only write to settings if data is correct
@@ -423,11 +1039,29 @@ pub(super) fn settings(app: &mut Cursive) {
                     SETTINGS.write().unwrap().config.image_command = image_command.to_string();
                     SETTINGS.write().unwrap().config.telnet_command = telnet_command.to_string();
                     SETTINGS.write().unwrap().config.textwrap = textwrap.to_string();
+                    if let Ok(minutes) = feed_update_interval.parse() {
+                        SETTINGS.write().unwrap().config.feed_update_interval = minutes;
+                    }
                     SETTINGS.write().unwrap().config.disable_history = disable_history;
                     SETTINGS.write().unwrap().config.disable_identities = disable_identities;
-                    let theme = if darkmode { "darkmode" } else { "lightmode" };
-                    app.load_toml(SETTINGS.read().unwrap().get_theme_by_name(theme.to_string())).unwrap();
-                    SETTINGS.write().unwrap().config.theme = theme.to_string();
+                    SETTINGS.write().unwrap().config.keep_raw_downloads = keep_raw_downloads;
+                    SETTINGS.write().unwrap().config.render_ansi_colors = render_ansi_colors;
+                    SETTINGS.write().unwrap().config.syntax_highlighting = syntax_highlighting;
+                    SETTINGS.write().unwrap().config.image_preview = image_preview;
+                    SETTINGS.write().unwrap().config.sixel_images = sixel_images;
+                    SETTINGS.write().unwrap().config.reading_list_offline = reading_list_offline;
+                    SETTINGS.write().unwrap().config.save_session = save_session;
+                    // Custom themes are kept unless darkmode is switched on
+                    let current_theme = SETTINGS.read().unwrap().config.theme.clone();
+                    let theme = match (darkmode, current_theme.as_str()) {
+                        (true, _) => "darkmode".to_string(),
+                        (false, "darkmode") => "lightmode".to_string(),
+                        (false, custom) => custom.to_string(),
+                    };
+                    if let Err(why) = SETTINGS.write().unwrap().set_theme(&theme) {
+                        warn!("{}", why);
+                    }
+                    app.load_toml(SETTINGS.read().unwrap().theme()).unwrap();
 
                     if let Err(why) = SETTINGS.write().unwrap().write_settings_to_file() {
                         app.add_layer(Dialog::info(format!("Could not write config file: {}", why)));
@@ -0,0 +1,188 @@
+use crate::controller::Controller;
+use crate::gophermap::GopherMapEntry;
+use crate::ui::{layout::Layout, setup};
+use cursive::event::{Event, Key};
+use cursive::theme::ColorStyle;
+use cursive::views::{NamedView, ResizedView, ScrollView, SelectView};
+use cursive::{Cursive, Printer, Rect};
+use std::ops::Range;
+use url::Url;
+
+// Link hints label every visible link of the page. Typing a label
+// follows the link without moving the selection there first.
+
+/// Keys used for the labels, all labels have the same length so no
+/// label is the start of another one
+const LABEL_KEYS: &str = "asdfghjkl";
+
+#[derive(Clone, Copy, Debug)]
+pub enum HintAction {
+    Open,
+    OpenInNewTab,
+    ShowUrl,
+}
+
+/// Result of typing a key while the hints are shown
+pub enum HintInput {
+    /// The key is the start of a label
+    Pending,
+    /// The label of the link in this row was typed
+    Chosen(usize),
+    /// No label starts with the typed keys
+    NoMatch,
+}
+
+struct Hint {
+    label: String,
+    /// Row of the link in the content view
+    row: usize,
+    /// Row of the link on the screen
+    y: usize,
+}
+
+pub struct Hints {
+    pub action: HintAction,
+    hints: Vec<Hint>,
+    typed: String,
+}
+
+impl Hints {
+    /// Labels the links in `rows`, the rows start at the first row
+    /// shown on the screen
+    fn new(action: HintAction, rows: Vec<usize>, first: usize) -> Hints {
+        let hints = labels(rows.len())
+            .into_iter()
+            .zip(rows)
+            .map(|(label, row)| Hint {
+                label,
+                row,
+                y: row - first,
+            })
+            .collect();
+        Hints {
+            action,
+            hints,
+            typed: String::new(),
+        }
+    }
+
+    pub fn input(&mut self, c: char) -> HintInput {
+        self.typed.push(c);
+        let mut matching = self
+            .hints
+            .iter()
+            .filter(|h| h.label.starts_with(&self.typed));
+        match matching.next() {
+            Some(hint) if hint.label == self.typed => HintInput::Chosen(hint.row),
+            Some(_) => HintInput::Pending,
+            None => HintInput::NoMatch,
+        }
+    }
+
+    /// Draws the labels that start with the typed keys at the left
+    /// edge of the content
+    pub fn draw(&self, printer: &Printer<'_, '_>) {
+        for hint in &self.hints {
+            if let Some(rest) = hint.label.strip_prefix(&self.typed) {
+                printer.with_color(ColorStyle::highlight(), |printer| {
+                    printer.print((0, hint.y), &format!(" {} ", rest));
+                });
+            }
+        }
+    }
+}
+
+/// Returns `count` labels of equal length
+fn labels(count: usize) -> Vec<String> {
+    let keys: Vec<char> = LABEL_KEYS.chars().collect();
+    let mut length = 1;
+    while keys.len().pow(length) < count {
+        length += 1;
+    }
+    (0..count)
+        .map(|mut n| {
+            let mut label = vec![keys[0]; length as usize];
+            for c in label.iter_mut().rev() {
+                *c = keys[n % keys.len()];
+                n /= keys.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
+}
+
+/// Rows of a view with `len` rows that are visible in the viewport
+fn visible_rows(viewport: Rect, len: usize) -> Range<usize> {
+    viewport.top()..(viewport.top() + viewport.height()).min(len)
+}
+
+/// Shows hints for the links visible in the current view
+pub(crate) fn show(app: &mut Cursive, action: HintAction) {
+    let current_view = app
+        .call_on_name("main", |v: &mut Layout| v.get_current_view())
+        .expect("main layout missing");
+    let (rows, first) = match current_view.as_str() {
+        "content" => {
+            let viewport = app
+                .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                    "content_scroll",
+                )
+                .expect("gopher scroll view missing")
+                .content_viewport();
+            let view = app
+                .find_name::<SelectView<GopherMapEntry>>("content")
+                .expect("View content missing");
+            let rows: Vec<usize> = visible_rows(viewport, view.len())
+                .filter(
+                    |&row| matches!(view.get_item(row), Some((_, e)) if !e.item_type.is_inline()),
+                )
+                .collect();
+            (rows, viewport.top())
+        }
+        "gemini_content" => {
+            let viewport = app
+                .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                    "gemini_content_scroll",
+                )
+                .expect("gemini scroll view missing")
+                .content_viewport();
+            let view = app
+                .find_name::<SelectView<Option<Url>>>("gemini_content")
+                .expect("view gemini_content missing");
+            let rows: Vec<usize> = visible_rows(viewport, view.len())
+                .filter(|&row| matches!(view.get_item(row), Some((_, Some(_)))))
+                .collect();
+            (rows, viewport.top())
+        }
+        other => unreachable!("unknown view {} in main layout", other),
+    };
+    if rows.is_empty() {
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .set_message("No links visible");
+        return;
+    }
+    app.call_on_name("main", |v: &mut Layout| {
+        v.set_hints(Hints::new(action, rows, first))
+    })
+    .expect("main layout missing");
+}
+
+/// Selects the link in `row` and follows it
+pub(crate) fn follow(app: &mut Cursive, row: usize, action: HintAction) {
+    // the callbacks of the selection are not needed, the row is visible
+    app.call_on_name("content", |v: &mut SelectView<GopherMapEntry>| {
+        v.set_selection(row);
+    });
+    app.call_on_name("gemini_content", |v: &mut SelectView<Option<Url>>| {
+        v.set_selection(row);
+    });
+    match action {
+        // Submitting the row opens the link like pressing Enter
+        HintAction::Open => {
+            app.on_event(Event::Key(Key::Enter));
+        }
+        HintAction::OpenInNewTab => setup::open_in_new_tab(app),
+        HintAction::ShowUrl => setup::show_link(app),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::sixel;
+use cursive::theme::{Color, ColorStyle, ColorType};
+use cursive::traits::View;
+use cursive::vec::Vec2;
+use cursive::Printer;
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Sixel sequence of the image drawn in the last frame, written to the
+/// terminal by `write_sixel` once the frame is on the screen
+static PENDING_SIXEL: Mutex<Option<String>> = Mutex::new(None);
+
+/// Decodes a PNG, GIF or JPEG image. Only the first frame of animated
+/// GIFs is used.
+pub fn decode(data: &[u8]) -> Result<DynamicImage, String> {
+    image::load_from_memory(data).map_err(|err| err.to_string())
+}
+
+/// Shows an image scaled to the available space. Every cell shows two
+/// pixels as a half block with truecolor foreground and background.
+/// On terminals supporting sixel graphics the view only draws empty
+/// cells, the image is written over them after the frame.
+pub struct ImageView {
+    image: DynamicImage,
+    /// Pixel size of a terminal cell if sixel graphics are used
+    cell_size: Option<(u32, u32)>,
+    /// Size of the scaled image in cells
+    size: Vec2,
+    /// The image scaled to two pixels per cell
+    halfblocks: RgbaImage,
+    /// The scaled image encoded as sixel sequence
+    sixel: String,
+}
+
+impl ImageView {
+    pub fn new(image: DynamicImage) -> ImageView {
+        ImageView {
+            image,
+            cell_size: if sixel::supported() {
+                Some(sixel::cell_size())
+            } else {
+                None
+            },
+            size: Vec2::zero(),
+            halfblocks: RgbaImage::new(0, 0),
+            sixel: String::new(),
+        }
+    }
+
+    /// Returns true if the image is drawn with sixel graphics
+    fn uses_sixel(&self) -> bool {
+        self.cell_size.is_some()
+    }
+
+    /// Size of the image in pixels when scaled to fit into the given
+    /// number of cells, keeping its aspect ratio
+    fn pixel_size(&self, cells: Vec2) -> (u32, u32) {
+        let (cell_width, cell_height) = self.cell_size.unwrap_or((1, 2));
+        let max_width = cells.x as f64 * f64::from(cell_width);
+        let max_height = cells.y as f64 * f64::from(cell_height);
+        let (width, height) = (self.image.width(), self.image.height());
+        let mut scale = (max_width / f64::from(width)).min(max_height / f64::from(height));
+        if self.cell_size.is_some() {
+            // sixel images are not enlarged
+            scale = scale.min(1.0);
+        }
+        (
+            ((f64::from(width) * scale) as u32).max(1),
+            ((f64::from(height) * scale) as u32).max(1),
+        )
+    }
+
+    /// Number of cells needed for an image of the given pixel size
+    fn cells(&self, (width, height): (u32, u32)) -> Vec2 {
+        let (cell_width, cell_height) = self.cell_size.unwrap_or((1, 2));
+        Vec2::new(
+            width.div_ceil(cell_width) as usize,
+            height.div_ceil(cell_height) as usize,
+        )
+    }
+
+    fn color(pixel: &Rgba<u8>) -> Option<Color> {
+        if pixel[3] < 128 {
+            None
+        } else {
+            Some(Color::Rgb(pixel[0], pixel[1], pixel[2]))
+        }
+    }
+}
+
+impl View for ImageView {
+    fn draw(&self, printer: &Printer<'_, '_>) {
+        let offset = printer.size.saturating_sub(self.size) / 2;
+        if self.uses_sixel() {
+            // Empty cells are not redrawn by curses, so the image stays
+            // visible once the screen has been refreshed
+            for y in 0..self.size.y {
+                printer.print_hline(offset + (0, y), self.size.x, " ");
+            }
+            let position = printer.offset + offset;
+            // Save the cursor, move to the top left corner of the image
+            // and restore the cursor afterwards
+            *PENDING_SIXEL.lock().unwrap() = Some(format!(
+                "\x1b7\x1b[{};{}H{}\x1b8",
+                position.y + 1,
+                position.x + 1,
+                self.sixel
+            ));
+            return;
+        }
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let top = Self::color(self.halfblocks.get_pixel(x as u32, 2 * y as u32));
+                let bottom = self
+                    .halfblocks
+                    .get_pixel_checked(x as u32, 2 * y as u32 + 1)
+                    .and_then(Self::color);
+                let (text, fg, bg) = match (top, bottom) {
+                    (Some(top), bottom) => ("▀", top, bottom),
+                    (None, Some(bottom)) => ("▄", bottom, None),
+                    (None, None) => continue,
+                };
+                let bg = bg.map_or(ColorType::InheritParent, ColorType::Color);
+                printer.with_color(ColorStyle::new(fg, bg), |printer| {
+                    printer.print(offset + (x, y), text);
+                });
+            }
+        }
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        let (width, height) = self.pixel_size(size);
+        let cells = self.cells((width, height));
+        if cells == self.size {
+            return;
+        }
+        self.size = cells;
+        let scaled = self
+            .image
+            .resize_exact(width, height, FilterType::Triangle)
+            .to_rgba8();
+        if self.uses_sixel() {
+            self.sixel = sixel::encode(&scaled);
+        } else {
+            self.halfblocks = scaled;
+        }
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.cells(self.pixel_size(constraint))
+    }
+}
+
+/// Writes the sixel image drawn in the last frame to the terminal. Call
+/// this after the frame has been refreshed, so curses does not paint
+/// over the image.
+pub fn write_sixel() {
+    if let Some(sixel) = PENDING_SIXEL.lock().unwrap().take() {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(sixel.as_bytes())
+            .and_then(|_| stdout.flush())
+            .ok();
+    }
+}
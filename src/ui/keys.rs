@@ -0,0 +1,283 @@
+use crate::settings::Binding;
+use cursive::event::{Event, EventTrigger, Key};
+use cursive::Cursive;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+// Dispatches key sequences to actions. Every key of a bound sequence
+// is registered as global callback, so keys are only seen here if no
+// view consumed them. Digits typed before a sequence are a count for
+// actions that can be repeated, like "5j". Any other key, or a key a
+// view consumed, drops the keys typed so far.
+
+/// Upper limit for counts, so a mistyped count does not block the UI
+const MAX_COUNT: usize = 1000;
+
+type Callback = Arc<dyn Fn(&mut Cursive) + Send + Sync>;
+
+struct KeyBinding {
+    action: &'static str,
+    keys: Vec<Event>,
+    /// Run the callback as often as the count says
+    repeat: bool,
+    callback: Callback,
+}
+
+/// Keys typed so far
+#[derive(Default)]
+struct Pending {
+    keys: Vec<Event>,
+    count: Option<usize>,
+    /// Set when the last key reached the key map
+    seen: bool,
+}
+
+impl Pending {
+    fn clear(&mut self) {
+        self.keys.clear();
+        self.count = None;
+    }
+}
+
+#[derive(Default)]
+pub struct KeyMap {
+    bindings: Vec<KeyBinding>,
+    /// Key sequences that were not bound
+    errors: Vec<String>,
+}
+
+impl KeyMap {
+    pub fn new() -> KeyMap {
+        KeyMap::default()
+    }
+
+    /// Binds the key sequences to an action. Invalid sequences and
+    /// sequences conflicting with sequences bound before are skipped
+    /// and reported by `errors`.
+    pub fn bind<F>(&mut self, action: &'static str, binding: &Binding, callback: F)
+    where
+        F: Fn(&mut Cursive) + Send + Sync + 'static,
+    {
+        self.add(action, binding, false, Arc::new(callback));
+    }
+
+    /// Like `bind`, but a count typed before the keys runs the action
+    /// repeatedly
+    pub fn bind_repeated<F>(&mut self, action: &'static str, binding: &Binding, callback: F)
+    where
+        F: Fn(&mut Cursive) + Send + Sync + 'static,
+    {
+        self.add(action, binding, true, Arc::new(callback));
+    }
+
+    fn add(&mut self, action: &'static str, binding: &Binding, repeat: bool, callback: Callback) {
+        for sequence in binding.sequences() {
+            let keys = match parse_sequence(sequence) {
+                Ok(keys) => keys,
+                Err(err) => {
+                    self.errors.push(format!("{}: {}", action, err));
+                    continue;
+                }
+            };
+            // A sequence that starts another one would hide it
+            let conflict = self
+                .bindings
+                .iter()
+                .find(|b| b.keys.starts_with(&keys) || keys.starts_with(&b.keys));
+            match conflict {
+                Some(other) if other.action == action && other.keys == keys => continue,
+                Some(other) => {
+                    self.errors.push(format!(
+                        "{}: \"{}\" conflicts with a key of {}",
+                        action, sequence, other.action
+                    ));
+                    continue;
+                }
+                None => (),
+            }
+            self.bindings.push(KeyBinding {
+                action,
+                keys,
+                repeat,
+                callback: callback.clone(),
+            });
+        }
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Registers global callbacks for all bound keys and for the digits
+    /// of counts. Digits that are bound themselves cannot be used in
+    /// counts. A callback run before the views see a key resets the
+    /// pending keys if the previous key did not reach the key map.
+    pub fn register(self, app: &mut Cursive) {
+        let events: HashSet<Event> = self
+            .bindings
+            .iter()
+            .flat_map(|b| b.keys.iter().cloned())
+            .collect();
+        let keymap = Arc::new(self);
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        for event in &events {
+            let event = event.clone();
+            let keymap = keymap.clone();
+            let pending = pending.clone();
+            app.add_global_callback(event.clone(), move |app| {
+                keymap.on_key(app, &pending, event.clone());
+            });
+        }
+        for digit in '0'..='9' {
+            if events.contains(&Event::Char(digit)) {
+                continue;
+            }
+            let pending = pending.clone();
+            app.add_global_callback(digit, move |_| on_digit(&pending, digit));
+        }
+        app.set_on_pre_event_inner(EventTrigger::any(), move |event| {
+            if matches!(
+                event,
+                Event::Refresh | Event::WindowResize | Event::FocusLost
+            ) {
+                return None;
+            }
+            let mut state = pending.lock().unwrap();
+            if !state.seen {
+                state.clear();
+            }
+            state.seen = false;
+            None
+        });
+    }
+
+    fn on_key(&self, app: &mut Cursive, pending: &Mutex<Pending>, event: Event) {
+        let mut state = pending.lock().unwrap();
+        state.seen = true;
+        state.keys.push(event.clone());
+        if !self.is_prefix(&state.keys) {
+            // the key does not continue the sequence but may start a
+            // new one
+            state.keys = vec![event];
+            state.count = None;
+            if !self.is_prefix(&state.keys) {
+                state.keys.clear();
+                return;
+            }
+        }
+        let binding = match self.bindings.iter().find(|b| b.keys == state.keys) {
+            Some(binding) => binding,
+            // wait for the next key of the sequence
+            None => return,
+        };
+        let count = match binding.repeat {
+            true => state.count.unwrap_or(1).min(MAX_COUNT),
+            false => 1,
+        };
+        state.clear();
+        drop(state);
+        for _ in 0..count {
+            (binding.callback)(app);
+        }
+    }
+
+    fn is_prefix(&self, keys: &[Event]) -> bool {
+        self.bindings.iter().any(|b| b.keys.starts_with(keys))
+    }
+}
+
+fn on_digit(pending: &Mutex<Pending>, digit: char) {
+    let mut state = pending.lock().unwrap();
+    state.seen = true;
+    state.keys.clear();
+    let value = digit.to_digit(10).unwrap_or(0) as usize;
+    state.count = match state.count {
+        Some(count) => Some(count.saturating_mul(10).saturating_add(value)),
+        // counts do not start with zero
+        None if value == 0 => None,
+        None => Some(value),
+    };
+}
+
+/// Parses a key sequence like "g g" into its keys
+pub fn parse_sequence(sequence: &str) -> Result<Vec<Event>, String> {
+    let keys = sequence
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(keys)
+}
+
+/// Parses a key like "x", "C-f", "M-left" or "f5". Modifiers are C-
+/// for Ctrl, M- or A- for Alt and S- for Shift.
+fn parse_key(key: &str) -> Result<Event, String> {
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+    let mut name = key;
+    while name.len() > 2 {
+        match name.get(..2) {
+            Some("C-") => ctrl = true,
+            Some("M-") | Some("A-") => alt = true,
+            Some("S-") => shift = true,
+            _ => break,
+        }
+        name = &name[2..];
+    }
+
+    let mut chars = name.chars();
+    let single = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    };
+    let named = match name.to_lowercase().as_str() {
+        _ if single.is_some() => None,
+        "space" => return modified_char(' ', ctrl, alt, shift, key),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "home" => Some(Key::Home),
+        "end" => Some(Key::End),
+        "pageup" | "pgup" => Some(Key::PageUp),
+        "pagedown" | "pgdn" => Some(Key::PageDown),
+        "tab" => Some(Key::Tab),
+        "enter" | "return" => Some(Key::Enter),
+        "esc" | "escape" => Some(Key::Esc),
+        "backspace" => Some(Key::Backspace),
+        "del" | "delete" => Some(Key::Del),
+        "ins" | "insert" => Some(Key::Ins),
+        other => other
+            .strip_prefix('f')
+            .and_then(|n| n.parse().ok())
+            .filter(|n| (1..=12).contains(n))
+            .map(Key::from_f),
+    };
+    match (named, single) {
+        (Some(k), _) => match (ctrl, alt, shift) {
+            (false, false, false) => Ok(Event::Key(k)),
+            (true, false, false) => Ok(Event::Ctrl(k)),
+            (false, true, false) => Ok(Event::Alt(k)),
+            (false, false, true) => Ok(Event::Shift(k)),
+            (true, true, false) => Ok(Event::CtrlAlt(k)),
+            (true, false, true) => Ok(Event::CtrlShift(k)),
+            (false, true, true) => Ok(Event::AltShift(k)),
+            (true, true, true) => Err(format!("too many modifiers in \"{}\"", key)),
+        },
+        (None, Some(c)) => modified_char(c, ctrl, alt, shift, key),
+        (None, None) => Err(format!("unknown key \"{}\"", key)),
+    }
+}
+
+fn modified_char(c: char, ctrl: bool, alt: bool, shift: bool, key: &str) -> Result<Event, String> {
+    match (ctrl, alt, shift) {
+        (false, false, false) => Ok(Event::Char(c)),
+        (false, false, true) => Ok(Event::Char(c.to_ascii_uppercase())),
+        (true, false, false) if c.is_ascii_alphabetic() => {
+            Ok(Event::CtrlChar(c.to_ascii_lowercase()))
+        }
+        (false, true, false) => Ok(Event::AltChar(c)),
+        _ => Err(format!("unsupported key \"{}\"", key)),
+    }
+}
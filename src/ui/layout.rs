@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use cursive::align::HAlign;
 use cursive::direction::Direction;
-use cursive::event::{AnyCb, Event, EventResult};
+use cursive::event::{AnyCb, Event, EventResult, Key};
 use cursive::theme::ColorStyle;
 use cursive::traits::View;
 use cursive::vec::Vec2;
@@ -11,6 +11,11 @@ use cursive::views::EditView;
 use cursive::Printer;
 use unicode_width::UnicodeWidthStr;
 
+use crate::ui::hints::{self, HintInput, Hints};
+
+/// Number of lines kept in the command line history
+const COMMAND_HISTORY_LEN: usize = 100;
+
 struct Screen {
     title: String,
     view: Box<dyn View>,
@@ -22,7 +27,19 @@ pub struct Layout {
     statusbar: Box<dyn View>,
     pub search: EditView,
     search_focused: bool,
+    /// Lines entered on the command line, oldest first
+    commands: Vec<String>,
+    /// Position in `commands` while browsing them with Up and Down
+    command_index: Option<usize>,
+    /// Completions of the command line, cycled with Tab
+    completions: Vec<String>,
+    completion_index: usize,
+    /// Link hints shown over the content
+    hints: Option<Hints>,
     focus: Option<String>,
+    /// Labels of the tabs, the tab bar is hidden if there is only one
+    tabs: Vec<String>,
+    current_tab: usize,
     screenchange: bool,
     last_size: Vec2,
     //    theme: Theme,
@@ -36,7 +53,14 @@ impl Layout {
             statusbar: status.into_boxed_view(),
             search: EditView::new(),
             search_focused: false,
+            commands: Vec::new(),
+            command_index: None,
+            completions: Vec::new(),
+            completion_index: 0,
+            hints: None,
             focus: None,
+            tabs: Vec::new(),
+            current_tab: 0,
             screenchange: true,
             last_size: Vec2::new(0, 0),
             // theme,
@@ -63,15 +87,93 @@ impl Layout {
         self.focus = Some(s);
         self.screenchange = true;
         self.search_focused = false;
+        self.hints = None;
         self.stack.clear();
     }
 
+    pub fn set_hints(&mut self, hints: Hints) {
+        self.hints = Some(hints);
+    }
+
+    /// Handles a key while link hints are shown. Typing a label follows
+    /// the link, any other key hides the hints.
+    fn on_hint_event(&mut self, event: Event) -> EventResult {
+        let hints = match self.hints.as_mut() {
+            Some(hints) => hints,
+            None => return EventResult::Ignored,
+        };
+        let input = match event {
+            Event::Char(c) => hints.input(c),
+            _ => HintInput::NoMatch,
+        };
+        match input {
+            HintInput::Pending => EventResult::Consumed(None),
+            HintInput::Chosen(row) => {
+                let action = hints.action;
+                self.hints = None;
+                EventResult::with_cb(move |app| hints::follow(app, row, action))
+            }
+            HintInput::NoMatch => {
+                self.hints = None;
+                EventResult::Consumed(None)
+            }
+        }
+    }
+
+    /// Lays the views out again at the last size, so content that was
+    /// just added is measured before the next frame is drawn
+    pub fn relayout(&mut self) {
+        if self.last_size != Vec2::zero() {
+            self.layout(self.last_size);
+        }
+    }
+
     pub fn set_title(&mut self, id: String, title: String) {
         if let Some(view) = self.views.get_mut(&id) {
             view.title = title;
         }
     }
 
+    pub fn set_tabs(&mut self, tabs: Vec<String>, current: usize) {
+        self.tabs = tabs;
+        self.current_tab = current;
+    }
+
+    /// Number of rows above the screen content
+    fn header_height(&self) -> usize {
+        if self.tabs.len() > 1 {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn draw_tabs(&self, printer: &Printer<'_, '_>) {
+        let labels: Vec<String> = self
+            .tabs
+            .iter()
+            .enumerate()
+            .map(|(i, label)| format!(" {}:{} ", i + 1, label))
+            .collect();
+        // scroll the tab bar so the current tab is visible
+        let mut first = self.current_tab;
+        let mut width = labels[first].width();
+        while first > 0 && width + labels[first - 1].width() <= printer.size.x {
+            first -= 1;
+            width += labels[first].width();
+        }
+        let mut x = 0;
+        for (i, label) in labels.iter().enumerate().skip(first) {
+            let style = if i == self.current_tab {
+                ColorStyle::highlight()
+            } else {
+                ColorStyle::title_secondary()
+            };
+            printer.with_color(style, |printer| printer.print((x, 0), label));
+            x += label.width();
+        }
+    }
+
     fn get_current_screen(&self) -> &Screen {
         if !self.stack.is_empty() {
             self.stack.last().unwrap()
@@ -108,9 +210,81 @@ impl Layout {
         }
     }
 
+    pub fn enable_command(&mut self) {
+        if !self.search_focused {
+            self.search.set_content(":");
+            self.search_focused = true;
+            self.command_index = None;
+        }
+    }
+
     pub fn clear_search(&mut self) {
         self.search.set_content("");
         self.search_focused = false;
+        self.completions.clear();
+    }
+
+    /// Remembers a line entered on the command line
+    pub fn add_command(&mut self, line: &str) {
+        if self.commands.last().map(String::as_str) != Some(line) {
+            self.commands.push(line.to_string());
+        }
+        if self.commands.len() > COMMAND_HISTORY_LEN {
+            self.commands.remove(0);
+        }
+    }
+
+    /// Shows the first completion of the command line, the others are
+    /// shown by pressing Tab again
+    pub fn set_completions(&mut self, completions: Vec<String>) {
+        if let Some(first) = completions.first() {
+            self.search.set_content(first.as_str());
+        }
+        self.completions = completions;
+        self.completion_index = 0;
+    }
+
+    fn show_command(&mut self, index: Option<usize>) {
+        self.command_index = index;
+        let line = index.map_or("", |i| self.commands[i].as_str());
+        self.search.set_content(format!(":{}", line));
+    }
+
+    /// Handles the keys of the command line that the edit view does not
+    /// know about
+    fn on_command_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Key(Key::Tab) if self.completions.len() > 1 => {
+                self.completion_index = (self.completion_index + 1) % self.completions.len();
+                let completion = self.completions[self.completion_index].clone();
+                self.search.set_content(completion);
+                EventResult::Consumed(None)
+            }
+            Event::Key(Key::Tab) => EventResult::with_cb(crate::ui::commandline::complete),
+            Event::Key(Key::Up) => {
+                self.completions.clear();
+                let index = match self.command_index {
+                    None => self.commands.len().checked_sub(1),
+                    Some(i) => Some(i.saturating_sub(1)),
+                };
+                if index.is_some() {
+                    self.show_command(index);
+                }
+                EventResult::Consumed(None)
+            }
+            Event::Key(Key::Down) => {
+                self.completions.clear();
+                if let Some(i) = self.command_index {
+                    let index = Some(i + 1).filter(|&i| i < self.commands.len());
+                    self.show_command(index);
+                }
+                EventResult::Consumed(None)
+            }
+            event => {
+                self.completions.clear();
+                self.search.on_event(event)
+            }
+        }
     }
 }
 
@@ -118,24 +292,36 @@ impl View for Layout {
     fn draw(&self, printer: &Printer<'_, '_>) {
         let search_visible = !self.search.get_content().is_empty();
         let screen = self.get_current_screen();
+        let header = self.header_height();
+        if header > 1 {
+            self.draw_tabs(printer);
+        }
         // screen title
         printer.with_color(ColorStyle::title_primary(), |printer| {
             let offset = HAlign::Center.get_offset(screen.title.width(), printer.size.x);
-            printer.print((offset, 0), &screen.title);
+            printer.print((offset, header - 1), &screen.title);
 
             if !self.stack.is_empty() {
-                printer.print((1, 0), "<");
+                printer.print((1, header - 1), "<");
             }
         });
 
         // screen content
         screen.view.draw(
             &printer
-                .offset((0, 1))
-                .cropped((printer.size.x, printer.size.y - 3))
+                .offset((0, header))
+                .cropped((printer.size.x, printer.size.y - header - 2))
                 .focused(true),
         );
 
+        if let Some(hints) = &self.hints {
+            hints.draw(
+                &printer
+                    .offset((0, header))
+                    .cropped((printer.size.x, printer.size.y - header - 2)),
+            );
+        }
+
         self.statusbar
             .draw(&printer.offset((0, printer.size.y - 2)));
 
@@ -146,14 +332,19 @@ impl View for Layout {
     }
 
     fn layout(&mut self, size: Vec2) {
+        // the hints were placed for the old size
+        if size != self.last_size {
+            self.hints = None;
+        }
         self.last_size = size;
 
         self.statusbar.layout(Vec2::new(size.x, 2));
         self.search.layout(Vec2::new(size.x, 1));
 
+        let header = self.header_height();
         self.get_current_screen_mut()
             .view
-            .layout(Vec2::new(size.x, size.y - 3));
+            .layout(Vec2::new(size.x, size.y - header - 2));
 
         // the focus view has changed, let the views know so they can redraw
         // their items
@@ -168,11 +359,18 @@ impl View for Layout {
 
     fn on_event(&mut self, event: Event) -> EventResult {
         let search_visible = !self.search.get_content().is_empty();
+        if self.hints.is_some() && !matches!(event, Event::Mouse { .. }) {
+            return self.on_hint_event(event);
+        }
         if let Event::Mouse { position, .. } = event {
+            self.hints = None;
             if position.y < self.last_size.y.saturating_sub(2) {
+                let header = self.header_height();
                 if let Some(ref id) = self.focus {
                     let screen = self.views.get_mut(id).unwrap();
-                    screen.view.on_event(event.relativized(Vec2::new(0, 1)));
+                    screen
+                        .view
+                        .on_event(event.relativized(Vec2::new(0, header)));
                 }
             } else if position.y < self.last_size.y {
                 self.statusbar
@@ -180,6 +378,8 @@ impl View for Layout {
             }
 
             EventResult::Consumed(None)
+        } else if search_visible && self.search.get_content().starts_with(':') {
+            self.on_command_event(event)
         } else if search_visible {
             self.search.on_event(event)
         } else {
@@ -1,4 +1,8 @@
+pub mod commandline;
 pub mod dialogs;
+pub mod hints;
+pub mod imageview;
+pub mod keys;
 pub mod layout;
 pub mod setup;
 pub mod statusbar;
@@ -1,18 +1,25 @@
 use crate::bookmarks::Bookmark;
 use crate::controller::{Controller, Direction};
+use crate::feeds::SourceKind;
 use crate::gophermap::{GopherMapEntry, ItemType};
 use crate::history::HistoryEntry;
-use crate::settings::default_keybindings;
-use crate::ui::{dialogs, layout::Layout, statusbar::StatusBar};
+use crate::settings::{default_keybindings, Binding};
+use crate::ui::{
+    commandline, dialogs,
+    hints::{self, HintAction},
+    keys::KeyMap,
+    layout::Layout,
+    statusbar::StatusBar,
+};
 use crate::SETTINGS;
 use cursive::{
-    event::Key,
+    event::{EventResult, Key},
     menu::Tree,
     view::{Nameable, Resizable, Scrollable},
     views::{
         Dialog, NamedView, OnEventView, ResizedView, ScrollView, SelectView, TextView, ViewRef,
     },
-    Cursive, View,
+    Cursive, Rect, View,
 };
 use url::Url;
 
@@ -32,33 +39,51 @@ fn render_help_text() -> String {
 |------------+--------------------------------|
 | Arrow keys | Move around in text            |
 | Enter      | Open the link under the cursor |
+|            | or fold a preformatted block   |
+| Left/Right | Scroll a preformatted block    |
 | Esc        | Go to menubar                  |
 | Space      | Scroll down one page           |
-| {}          | Open new URL                   |
-| {}          | Edit current URL               |
-| {}          | Navigate back                  |
-| {}          | Close application              |
-| {}          | Save current page              |
-| {}          | Reload current page            |
-| {}          | Show link under cursor         |
-| {}          | Add bookmark for current page  |
-| {}          | Go to next link                |
-| {}          | Go to previous link            |
-| {}          | Move one line down             |
-| {}          | Move one line up               |
-| {}          | Search in text                 |
-| {}          | Move to next search result     |
-| {}          | Move to previous search result |
-| {}          | Display this help text         |
-|------------+--------------------------------|"#,
+| {:<10} | Open new URL                   |
+| {:<10} | Edit current URL               |
+| {:<10} | Navigate back                  |
+| {:<10} | Navigate forward               |
+| {:<10} | Close application              |
+| {:<10} | Save current page              |
+| {:<10} | Reload current page            |
+| {:<10} | Show link under cursor         |
+| {:<10} | Add bookmark for current page  |
+| {:<10} | Add page to reading list       |
+| {:<10} | Go to next link                |
+| {:<10} | Go to previous link            |
+| {:<10} | Move one line down             |
+| {:<10} | Move one line up               |
+| {:<10} | Search in text                 |
+| {:<10} | Move to next search result     |
+| {:<10} | Move to previous search result |
+| {:<10} | Open link in a new tab         |
+| {:<10} | Show next tab                  |
+| {:<10} | Show previous tab              |
+| {:<10} | Close current tab              |
+| {:<10} | Open a link by its hint        |
+| {:<10} | Open a link in a tab by hint   |
+| {:<10} | Show the URL of a link by hint |
+| {:<10} | Enter a command                |
+| {:<10} | Display this help text         |
+|------------+--------------------------------|
+
+Moving, going to links and search results and switching tabs
+can be repeated by typing a count first, e.g. 5j moves five lines
+down."#,
         keybindings.open_new_url,
         keybindings.edit_current_url,
         keybindings.navigate_back,
+        keybindings.navigate_forward,
         keybindings.close,
         keybindings.save_page,
         keybindings.reload_page,
         keybindings.show_link,
         keybindings.add_bookmark,
+        keybindings.add_to_reading_list,
         keybindings.next_link,
         keybindings.previous_link,
         keybindings.move_down,
@@ -66,19 +91,34 @@ fn render_help_text() -> String {
         keybindings.search_in_text,
         keybindings.next_search_result,
         keybindings.previous_search_result,
+        keybindings.open_in_new_tab,
+        keybindings.next_tab,
+        keybindings.previous_tab,
+        keybindings.close_tab,
+        keybindings.hint_links,
+        keybindings.hint_links_new_tab,
+        keybindings.hint_links_show_url,
+        keybindings.command_line,
         keybindings.show_help,
     )
 }
 
 pub fn setup(app: &mut Cursive) {
     trace!("ui::setup");
-    setup_keys(app);
+    let errors = setup_keys(app);
     setup_menu(app);
     setup_ui(app);
+    if !errors.is_empty() {
+        app.add_layer(Dialog::info(format!(
+            "Some key bindings in the configuration were ignored:\n\n{}",
+            errors.join("\n")
+        )));
+    }
 }
 
-/// Register global keys.
-fn setup_keys(app: &mut Cursive) {
+/// Register global keys. Returns the problems with the configured key
+/// bindings.
+fn setup_keys(app: &mut Cursive) -> Vec<String> {
     app.set_autohide_menu(false);
 
     let keybindings = SETTINGS
@@ -89,145 +129,189 @@ fn setup_keys(app: &mut Cursive) {
         .clone()
         .unwrap_or(default_keybindings());
 
-    app.add_global_callback(Key::Esc, |app| {
+    let mut keys = KeyMap::new();
+    keys.bind("menubar", &Binding::key("esc"), |app| {
         app.call_on_name("main", |v: &mut Layout| v.clear_search())
             .expect("main layout missing");
         app.select_menubar()
     });
-    app.add_global_callback(keybindings.close, Cursive::quit);
-    app.add_global_callback(keybindings.open_new_url, dialogs::open_url);
-    app.add_global_callback(keybindings.edit_current_url, dialogs::open_current_url);
+    keys.bind("close", &keybindings.close, Cursive::quit);
+    keys.bind("open_new_url", &keybindings.open_new_url, dialogs::open_url);
+    keys.bind(
+        "edit_current_url",
+        &keybindings.edit_current_url,
+        dialogs::open_current_url,
+    );
 
-    app.add_global_callback(keybindings.navigate_back, |app| {
+    keys.bind("navigate_back", &keybindings.navigate_back, |app| {
         // step back history
+        let index = Controller::get_selected_item_index(app);
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .navigate_back(index);
+    });
+    keys.bind("navigate_forward", &keybindings.navigate_forward, |app| {
+        let index = Controller::get_selected_item_index(app);
         app.user_data::<Controller>()
             .expect("controller missing")
-            .navigate_back();
+            .navigate_forward(index);
     });
-    app.add_global_callback(keybindings.reload_page, |app| {
+    keys.bind("reload_page", &keybindings.reload_page, |app| {
         // reload the current page
         let index = Controller::get_selected_item_index(app);
         let controller = app.user_data::<Controller>().expect("controller missing");
         let current_url = controller.current_url.lock().unwrap().clone();
         controller.open_url(current_url, false, index);
     });
-    app.add_global_callback(keybindings.save_page, dialogs::save_as);
-    app.add_global_callback(keybindings.show_link, |app| {
-        // show info about currently selected line
-        let current_view = app
-            .call_on_name("main", |v: &mut Layout| v.get_current_view())
-            .expect("main layout missing");
-
-        match current_view.as_str() {
-            "content" => {
-                let view: ViewRef<SelectView<GopherMapEntry>> =
-                    app.find_name("content").expect("View content missing");
-                let cur = view.selected_id().unwrap_or(0);
-                if let Some((_, item)) = view.get_item(cur) {
-                    match item.item_type {
-                        ItemType::Html => {
-                            let mut url = item.url.to_string();
-                            if url.starts_with("URL:") {
-                                url.replace_range(..3, "");
-                            }
-                            app.user_data::<Controller>()
-                                .expect("controller missing")
-                                .set_message(&format!("URL '{}'", url));
-                        }
-                        ItemType::Inline => (),
-                        _ => app
-                            .user_data::<Controller>()
-                            .expect("controller missing")
-                            .set_message(&format!("URL '{}'", item.url)),
-                    }
-                };
-            }
-            "gemini_content" => {
-                let view: ViewRef<SelectView<Option<Url>>> = app
-                    .find_name("gemini_content")
-                    .expect("View gemini missing");
-                let cur = view.selected_id().unwrap_or(0);
-                if let Some((_, Some(url))) = view.get_item(cur) {
-                    app.user_data::<Controller>()
-                        .expect("controller missing")
-                        .set_message(&format!("URL '{}'", url));
-                }
-            }
-            other => unreachable!("unknown view {} in main layout", other),
-        }
-    });
-    app.add_global_callback(keybindings.move_down, |app| {
+    keys.bind("save_page", &keybindings.save_page, dialogs::save_as);
+    keys.bind("show_link", &keybindings.show_link, show_link);
+    keys.bind_repeated("move_down", &keybindings.move_down, |app| {
         // go to next line
         move_selection(app, Direction::Next);
     });
-    app.add_global_callback(keybindings.move_up, |app| {
+    keys.bind_repeated("move_up", &keybindings.move_up, |app| {
         // go to previous line
         move_selection(app, Direction::Previous);
     });
-    app.add_global_callback(keybindings.next_link /*Key::Tab*/, |app| {
+    keys.bind_repeated("next_link", &keybindings.next_link, |app| {
         // go to next link
         move_to_link(app, Direction::Next);
     });
-    app.add_global_callback(
-        keybindings.previous_link, /*Event::Shift(Key::Tab)*/
+    keys.bind_repeated(
+        "previous_link",
+        &keybindings.previous_link, /*Event::Shift(Key::Tab)*/
         |app| {
             // go to previous link
             move_to_link(app, Direction::Previous);
         },
     );
-    app.add_global_callback(keybindings.add_bookmark, dialogs::add_bookmark_current_url);
-    app.add_global_callback(keybindings.show_help, |s| {
+    keys.bind(
+        "add_bookmark",
+        &keybindings.add_bookmark,
+        dialogs::add_bookmark_current_url,
+    );
+    keys.bind(
+        "add_to_reading_list",
+        &keybindings.add_to_reading_list,
+        |app| {
+            let top = Controller::get_scroll_offset(app);
+            app.user_data::<Controller>()
+                .expect("controller missing")
+                .add_to_reading_list(top);
+        },
+    );
+    keys.bind("show_help", &keybindings.show_help, |s| {
         s.add_layer(
             Dialog::around(TextView::new(render_help_text().as_str()).scrollable())
                 .dismiss_button("Ok"),
         );
     });
-    app.add_global_callback(keybindings.search_in_text, move |app| {
-        app.call_on_name("main", |v: &mut Layout| v.enable_search())
-            .expect("main layout missing");
+    keys.bind(
+        "open_in_new_tab",
+        &keybindings.open_in_new_tab,
+        open_in_new_tab,
+    );
+    keys.bind_repeated("next_tab", &keybindings.next_tab, |app| {
+        let index = Controller::get_selected_item_index(app);
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .cycle_tabs(true, index);
     });
-    app.add_global_callback(keybindings.next_search_result, |app| {
-        let controller = app.user_data::<Controller>().expect("controller missing");
-        let hits = controller.current_search_results.clone();
-        if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
-            let scroll_view = app
-                .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
-                    "content_scroll",
-                )
-                .expect("gopher scroll view missing");
-            move_to_next_item(content, scroll_view, Direction::Next, hits);
-        } else if let Some(content) = app.find_name::<SelectView<Option<Url>>>("gemini_content") {
-            let scroll_view = app
-                .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
-                    "gemini_content_scroll",
-                )
-                .expect("gemini scroll view missing");
-            move_to_next_item(content, scroll_view, Direction::Next, hits);
-        } else {
-            unreachable!("view content and gemini_content missing");
-        }
+    keys.bind_repeated("previous_tab", &keybindings.previous_tab, |app| {
+        let index = Controller::get_selected_item_index(app);
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .cycle_tabs(false, index);
     });
-    app.add_global_callback(keybindings.previous_search_result, |app| {
-        let controller = app.user_data::<Controller>().expect("controller missing");
-        let hits = controller.current_search_results.clone();
-        if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
-            let scroll_view = app
-                .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
-                    "content_scroll",
-                )
-                .expect("gopher scroll view missing");
-            move_to_next_item(content, scroll_view, Direction::Previous, hits);
-        } else if let Some(content) = app.find_name::<SelectView<Option<Url>>>("gemini_content") {
-            let scroll_view = app
-                .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
-                    "gemini_content_scroll",
-                )
-                .expect("gemini scroll view missing");
-            move_to_next_item(content, scroll_view, Direction::Previous, hits);
-        } else {
-            unreachable!("view content and gemini_content missing");
-        }
+    keys.bind("close_tab", &keybindings.close_tab, |app| {
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .close_tab();
+    });
+    keys.bind("hint_links", &keybindings.hint_links, |app| {
+        hints::show(app, HintAction::Open);
+    });
+    keys.bind(
+        "hint_links_new_tab",
+        &keybindings.hint_links_new_tab,
+        |app| {
+            hints::show(app, HintAction::OpenInNewTab);
+        },
+    );
+    keys.bind(
+        "hint_links_show_url",
+        &keybindings.hint_links_show_url,
+        |app| {
+            hints::show(app, HintAction::ShowUrl);
+        },
+    );
+    keys.bind("command_line", &keybindings.command_line, |app| {
+        app.call_on_name("main", |v: &mut Layout| v.enable_command())
+            .expect("main layout missing");
+    });
+    keys.bind("search_in_text", &keybindings.search_in_text, move |app| {
+        app.call_on_name("main", |v: &mut Layout| v.enable_search())
+            .expect("main layout missing");
     });
+    keys.bind_repeated(
+        "next_search_result",
+        &keybindings.next_search_result,
+        |app| {
+            let controller = app.user_data::<Controller>().expect("controller missing");
+            let hits = controller.current_search_results.clone();
+            if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
+                let scroll_view = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                        "content_scroll",
+                    )
+                    .expect("gopher scroll view missing");
+                move_to_next_item(content, scroll_view, Direction::Next, hits);
+            } else if let Some(content) = app.find_name::<SelectView<Option<Url>>>("gemini_content")
+            {
+                let scroll_view = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                        "gemini_content_scroll",
+                    )
+                    .expect("gemini scroll view missing");
+                move_to_next_item(content, scroll_view, Direction::Next, hits);
+            } else {
+                unreachable!("view content and gemini_content missing");
+            }
+        },
+    );
+    keys.bind_repeated(
+        "previous_search_result",
+        &keybindings.previous_search_result,
+        |app| {
+            let controller = app.user_data::<Controller>().expect("controller missing");
+            let hits = controller.current_search_results.clone();
+            if let Some(content) = app.find_name::<SelectView<GopherMapEntry>>("content") {
+                let scroll_view = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                        "content_scroll",
+                    )
+                    .expect("gopher scroll view missing");
+                move_to_next_item(content, scroll_view, Direction::Previous, hits);
+            } else if let Some(content) = app.find_name::<SelectView<Option<Url>>>("gemini_content")
+            {
+                let scroll_view = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                        "gemini_content_scroll",
+                    )
+                    .expect("gemini scroll view missing");
+                move_to_next_item(content, scroll_view, Direction::Previous, hits);
+            } else {
+                unreachable!("view content and gemini_content missing");
+            }
+        },
+    );
+
+    let errors = keys.errors().to_vec();
+    for error in &errors {
+        warn!("Key binding ignored: {}", error);
+    }
+    keys.register(app);
+    errors
 }
 
 fn setup_menu(app: &mut Cursive) {
@@ -238,6 +322,11 @@ fn setup_menu(app: &mut Cursive) {
             .leaf("Open URL...", dialogs::open_url)
             .delimiter()
             .leaf("Save page as...", dialogs::save_as)
+            .leaf("Open in external browser", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .open_in_browser();
+            })
             .leaf("Settings...", dialogs::settings)
             .delimiter()
             .leaf("Quit", Cursive::quit),
@@ -246,6 +335,12 @@ fn setup_menu(app: &mut Cursive) {
         "History",
         Tree::new()
             .leaf("Show all history...", dialogs::edit_history)
+            .leaf("Session tree...", dialogs::session_tree)
+            .leaf("Show history page", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .open_url(Url::parse("about:history").unwrap(), true, 0);
+            })
             .leaf("Clear history", |app| {
                 app.user_data::<Controller>()
                     .expect("controller missing")
@@ -257,9 +352,64 @@ fn setup_menu(app: &mut Cursive) {
         "Bookmarks",
         Tree::new()
             .leaf("Edit...", dialogs::edit_bookmarks)
+            .leaf("Show all bookmarks", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .open_url(Url::parse("about:bookmarks").unwrap(), true, 0);
+            })
             .leaf("Add bookmark", dialogs::add_bookmark_current_url)
+            .leaf("Import...", dialogs::import_bookmarks)
+            .leaf("Export...", dialogs::export_bookmarks)
             .delimiter(),
     );
+    menubar.add_subtree(
+        "Reading list",
+        Tree::new()
+            .leaf("Show reading list...", dialogs::reading_list)
+            .leaf("Add this page", |app| {
+                let top = Controller::get_scroll_offset(app);
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .add_to_reading_list(top);
+            }),
+    );
+    menubar.add_subtree(
+        "Feeds",
+        Tree::new()
+            .leaf("Show new entries", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .open_url(Url::parse("about:feeds").unwrap(), true, 0);
+            })
+            .leaf("Subscribe to this page", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .subscribe_current_page();
+            })
+            .leaf("Manage subscriptions...", |app| {
+                dialogs::manage_subscriptions(app, SourceKind::Feed)
+            })
+            .delimiter()
+            .leaf("New in your phlogs", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .open_url(Url::parse("about:phlogs").unwrap(), true, 0);
+            })
+            .leaf("Watch this gophermap", |app| {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .watch_current_gophermap();
+            })
+            .leaf("Manage watched phlogs...", |app| {
+                dialogs::manage_subscriptions(app, SourceKind::Phlog)
+            })
+            .delimiter()
+            .leaf("Update now", |app| {
+                let controller = app.user_data::<Controller>().expect("controller missing");
+                controller.set_message("Updating feeds and phlogs...");
+                controller.update_feeds();
+            }),
+    );
     menubar.add_subtree(
         "Identities",
         Tree::new()
@@ -316,16 +466,20 @@ fn setup_ui(app: &mut Cursive) {
         .with_name("content")
         .full_width()
         .scrollable()
+        .on_scroll_inner(end_of_page)
         .with_name("content_scroll");
     let event_view = OnEventView::new(scrollable).on_event(' ', |app| {
-        app.call_on_name(
+        let result = app.call_on_name(
             "content_scroll",
             |s: &mut ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>| {
                 let rect = s.content_viewport();
                 let bl = rect.bottom_left();
-                s.set_offset(bl);
+                s.set_offset(bl)
             },
         );
+        if let Some(result) = result {
+            result.process(app);
+        }
     });
 
     // Create gemini content view
@@ -334,17 +488,25 @@ fn setup_ui(app: &mut Cursive) {
         .with_name("gemini_content")
         .full_width()
         .scrollable()
+        .on_scroll_inner(end_of_page)
         .with_name("gemini_content_scroll");
-    let gemini_event_view = OnEventView::new(scrollable).on_event(' ', |app| {
-        app.call_on_name(
-            "gemini_content_scroll",
-            |s: &mut ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>| {
-                let rect = s.content_viewport();
-                let bl = rect.bottom_left();
-                s.set_offset(bl);
-            },
-        );
-    });
+    let gemini_event_view = OnEventView::new(scrollable)
+        .on_event(' ', |app| {
+            let result = app.call_on_name(
+                "gemini_content_scroll",
+                |s: &mut ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>| {
+                    let rect = s.content_viewport();
+                    let bl = rect.bottom_left();
+                    s.set_offset(bl)
+                },
+            );
+            if let Some(result) = result {
+                result.process(app);
+            }
+        })
+        // scroll wide preformatted blocks
+        .on_event(Key::Left, |app| scroll_preformatted(app, -8))
+        .on_event(Key::Right, |app| scroll_preformatted(app, 8));
     let status = StatusBar::new().with_name("statusbar");
     let mut layout = Layout::new(status /*, theme*/)
         .view("content", event_view, "Gophermap")
@@ -364,24 +526,46 @@ fn setup_ui(app: &mut Cursive) {
             app.call_on_name("main", |v: &mut Layout| {
                 v.clear_search();
             });
-            app.user_data::<Controller>()
-                .expect("controller missing")
-                .search(search_str[1..].to_string());
+            if let Some(line) = search_str.strip_prefix(':') {
+                app.call_on_name("main", |v: &mut Layout| v.add_command(line));
+                commandline::run(app, line);
+            } else {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .search(search_str[1..].to_string());
+            }
         });
     })
     .expect("main layout missing");
 }
 
-pub fn setup_bookmark_menu(app: &mut Cursive, bookmarks: &Vec<Bookmark>) {
-    // Add bookmarks to bookmark menu on startup
-    info!("Adding existing bookmarks to menu");
+/// Number of fixed items at the top of the history menu
+pub(crate) const HISTORY_MENU_ITEMS: usize = 5;
+
+/// Number of fixed items at the top of the bookmarks menu
+const BOOKMARK_MENU_ITEMS: usize = 6;
+
+/// (Re)builds the bookmarks in the bookmark menu. Bookmarks in folders
+/// are shown in nested submenus.
+pub fn setup_bookmark_menu(app: &mut Cursive, bookmarks: &[Bookmark]) {
+    info!("Adding bookmarks to menu");
     let menutree = app
         .menubar()
         .find_subtree("Bookmarks")
         .expect("bookmarks menu missing");
+    while menutree.len() > BOOKMARK_MENU_ITEMS {
+        menutree.remove(BOOKMARK_MENU_ITEMS);
+    }
     for entry in bookmarks {
+        let mut tree = &mut *menutree;
+        for folder in entry.folders() {
+            if tree.find_subtree(folder).is_none() {
+                tree.add_subtree(folder, Tree::new());
+            }
+            tree = tree.find_subtree(folder).expect("folder missing");
+        }
         let url = entry.url.clone();
-        menutree.insert_leaf(3, &entry.title, move |app| {
+        tree.add_leaf(&entry.title, move |app| {
             app.user_data::<Controller>()
                 .expect("controller missing")
                 .open_url(url.clone(), true, 0);
@@ -389,16 +573,19 @@ pub fn setup_bookmark_menu(app: &mut Cursive, bookmarks: &Vec<Bookmark>) {
     }
 }
 
-pub fn setup_history_menu(app: &mut Cursive, entries: &Vec<HistoryEntry>) {
-    // Add old entries to history on start-up
+/// (Re)builds the latest history entries in the history menu, newest
+/// entry first
+pub fn setup_history_menu(app: &mut Cursive, entries: &[HistoryEntry]) {
     let menutree = app
         .menubar()
         .find_subtree("History")
         .expect("history menu missing");
+    while menutree.len() > HISTORY_MENU_ITEMS {
+        menutree.remove(HISTORY_MENU_ITEMS);
+    }
     for entry in entries {
-        let title = entry.title.clone();
         let url = entry.url.clone();
-        menutree.insert_leaf(3, &title, move |app| {
+        menutree.add_leaf(&entry.title, move |app| {
             app.user_data::<Controller>()
                 .expect("controller missing")
                 .open_url(url.clone(), true, 0);
@@ -408,6 +595,61 @@ pub fn setup_history_menu(app: &mut Cursive, entries: &Vec<HistoryEntry>) {
 
 //--------- interface manipulation functions ---------------------------
 
+/// Shows the URL of the selected link in the status bar
+pub(crate) fn show_link(app: &mut Cursive) {
+    let current_view = app
+        .call_on_name("main", |v: &mut Layout| v.get_current_view())
+        .expect("main layout missing");
+
+    match current_view.as_str() {
+        "content" => {
+            let view: ViewRef<SelectView<GopherMapEntry>> =
+                app.find_name("content").expect("View content missing");
+            let cur = view.selected_id().unwrap_or(0);
+            if let Some((_, item)) = view.get_item(cur) {
+                match item.item_type {
+                    ItemType::Html => {
+                        let mut url = item.url.to_string();
+                        if url.starts_with("URL:") {
+                            url.replace_range(..3, "");
+                        }
+                        app.user_data::<Controller>()
+                            .expect("controller missing")
+                            .set_message(&format!("URL '{}'", url));
+                    }
+                    ItemType::Inline => (),
+                    _ => app
+                        .user_data::<Controller>()
+                        .expect("controller missing")
+                        .set_message(&format!("URL '{}'", item.url)),
+                }
+            };
+        }
+        "gemini_content" => {
+            let view: ViewRef<SelectView<Option<Url>>> = app
+                .find_name("gemini_content")
+                .expect("View gemini missing");
+            let cur = view.selected_id().unwrap_or(0);
+            if let Some((_, Some(url))) = view.get_item(cur) {
+                app.user_data::<Controller>()
+                    .expect("controller missing")
+                    .set_message(&format!("URL '{}'", url));
+            }
+        }
+        other => unreachable!("unknown view {} in main layout", other),
+    }
+}
+
+/// Opens the selected link in a new tab
+pub(crate) fn open_in_new_tab(app: &mut Cursive) {
+    let link = Controller::get_selected_link(app);
+    let controller = app.user_data::<Controller>().expect("controller missing");
+    match link {
+        Some(url) => controller.open_in_new_tab(url),
+        None => controller.set_message("No link selected"),
+    }
+}
+
 fn move_selection(app: &mut Cursive, dir: Direction) {
     let current_view = app
         .find_name::<Layout>("main")
@@ -425,11 +667,13 @@ fn move_selection(app: &mut Cursive, dir: Direction) {
             };
             callback(app);
             if let Some(id) = view.selected_id() {
-                app.find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
-                    "content_scroll",
-                )
-                .expect("gopher scroll view missing")
-                .set_offset(cursive::Vec2::new(0, id));
+                let result = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<GopherMapEntry>>>>>(
+                        "content_scroll",
+                    )
+                    .expect("gopher scroll view missing")
+                    .set_offset(cursive::Vec2::new(0, id));
+                result.process(app);
             }
         }
         "gemini_content" => {
@@ -442,17 +686,45 @@ fn move_selection(app: &mut Cursive, dir: Direction) {
             };
             callback(app);
             if let Some(id) = view.selected_id() {
-                app.find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
-                    "gemini_content_scroll",
-                )
-                .expect("gemini scroll view missing")
-                .set_offset(cursive::Vec2::new(0, id));
+                let result = app
+                    .find_name::<ScrollView<ResizedView<NamedView<SelectView<Option<Url>>>>>>(
+                        "gemini_content_scroll",
+                    )
+                    .expect("gemini scroll view missing")
+                    .set_offset(cursive::Vec2::new(0, id));
+                result.process(app);
             }
         }
         other => unreachable!("unknown view {} in main layout", other),
     }
 }
 
+/// Scroll callback of the content views. Pages in the reading list
+/// are marked as read once their end is visible.
+fn end_of_page<V: View>(view: &mut ScrollView<V>, _: Rect) -> EventResult {
+    if view.is_at_bottom() {
+        EventResult::with_cb(|app| {
+            app.user_data::<Controller>()
+                .expect("controller missing")
+                .reached_end_of_page();
+        })
+    } else {
+        EventResult::Ignored
+    }
+}
+
+fn scroll_preformatted(app: &mut Cursive, columns: isize) {
+    let row = app
+        .find_name::<SelectView<Option<Url>>>("gemini_content")
+        .expect("View gemini_content missing")
+        .selected_id();
+    if let Some(row) = row {
+        app.user_data::<Controller>()
+            .expect("controller missing")
+            .scroll_preformatted(row, columns);
+    }
+}
+
 fn move_to_link(app: &mut Cursive, dir: Direction) {
     let current_view = app
         .find_name::<Layout>("main")